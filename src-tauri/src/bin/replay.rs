@@ -0,0 +1,64 @@
+// Replays a trace recorded with MACROPAD_TRACE set, printing what each report resolves to in a profile
+// Usage: replay <trace> <profile name or window title> [config]
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+
+use macropad_console_lib::config::{get_config_path, AppConfig};
+use macropad_console_lib::trace::{format_report, parse_trace, replay_trace};
+
+fn main() -> Result<()> {
+    let args = std::env::args().collect::<Vec<String>>();
+    let (trace_path, profile_name) = match &args[1..] {
+        [trace_path, profile_name, ..] => (trace_path, profile_name),
+        _ => bail!("Usage: replay <trace> <profile name or window title> [config]"),
+    };
+    let config_path = args
+        .get(3)
+        .map(PathBuf::from)
+        .unwrap_or_else(get_config_path);
+
+    let config: AppConfig = serde_json::from_str(&std::fs::read_to_string(config_path)?)?;
+    let profile = config
+        .application_profiles
+        .get(profile_name)
+        .or_else(|| {
+            config
+                .get_application_profile(profile_name)
+                .map(|(_, profile)| profile)
+        })
+        .ok_or_else(|| anyhow!("No profile for {}", profile_name))?;
+
+    let entries = parse_trace(&std::fs::read_to_string(trace_path)?)?;
+    let first_timestamp = entries.first().map(|entry| entry.timestamp).unwrap_or(0);
+
+    for step in replay_trace(&entries, config.debounce.clone(), profile) {
+        let resolved = match step.resolution {
+            Some(resolution) => {
+                let key_combination = serde_json::to_value(&resolution.key_combination)?;
+                let key_combination = key_combination.as_str().unwrap_or_default();
+                match resolution.command {
+                    Some(command) if resolution.release => {
+                        format!("{} -> release {}", key_combination, command.display_name)
+                    }
+                    Some(command) => format!(
+                        "{} -> {} x{}",
+                        key_combination, command.display_name, resolution.repetitions
+                    ),
+                    None => format!("{} -> unbound", key_combination),
+                }
+            }
+            None => "".to_string(),
+        };
+
+        println!(
+            "{:>8}ms  {:<12} {:?} {}",
+            step.timestamp.saturating_sub(first_timestamp),
+            format_report(&step.report),
+            step.action,
+            resolved
+        );
+    }
+
+    Ok(())
+}
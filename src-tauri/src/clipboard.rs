@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+use arboard::ImageData;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TextTransform {
+    Uppercase,
+    Lowercase,
+    Trim,
+    // Replaces every match, the replacement can refer to groups as $1 or ${name}
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+    },
+}
+
+pub fn apply_transform(text: &str, transform: &TextTransform) -> Result<String> {
+    Ok(match transform {
+        TextTransform::Uppercase => text.to_uppercase(),
+        TextTransform::Lowercase => text.to_lowercase(),
+        TextTransform::Trim => text.trim().to_string(),
+        TextTransform::RegexReplace {
+            pattern,
+            replacement,
+        } => Regex::new(pattern)?
+            .replace_all(text, replacement.as_str())
+            .into_owned(),
+    })
+}
+
+enum SavedContents {
+    Text(String),
+    Image(ImageData<'static>),
+    Empty,
+}
+
+// The system clipboard and what ClipboardSave stored
+// The clipboard is kept open as on Linux what was set is lost when it is closed
+#[derive(Default)]
+pub struct ClipboardState {
+    clipboard: Option<arboard::Clipboard>,
+    saved: Option<SavedContents>,
+}
+
+impl ClipboardState {
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new()?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+
+    pub fn set_text(&mut self, text: &str) -> Result<()> {
+        Ok(self.clipboard()?.set_text(text)?)
+    }
+
+    pub fn get_text(&mut self) -> Result<String> {
+        Ok(self.clipboard()?.get_text()?)
+    }
+
+    // Text and images are saved, anything else is saved as an empty clipboard
+    pub fn save(&mut self) -> Result<()> {
+        let clipboard = self.clipboard()?;
+        let contents = match clipboard.get_text() {
+            Ok(text) => SavedContents::Text(text),
+            Err(_) => match clipboard.get_image() {
+                Ok(image) => SavedContents::Image(image.to_owned_img()),
+                Err(_) => SavedContents::Empty,
+            },
+        };
+
+        self.saved = Some(contents);
+        Ok(())
+    }
+
+    pub fn restore(&mut self) -> Result<()> {
+        let contents = self
+            .saved
+            .take()
+            .ok_or_else(|| anyhow!("Nothing saved to restore"))?;
+
+        let clipboard = self.clipboard()?;
+        match contents {
+            SavedContents::Text(text) => clipboard.set_text(text)?,
+            SavedContents::Image(image) => clipboard.set_image(image)?,
+            SavedContents::Empty => clipboard.clear()?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Modifier;
+use crate::toggle::ToggleStates;
+
+// Tested by If operations
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Condition {
+    // Regex searched for in the active window's title
+    WindowTitle { pattern: String },
+    // Regex searched for in the active window's executable name
+    AppName { pattern: String },
+    // An input written as in bindings, e.g. "BTN_4" or "ENC_0_PUSH", is held
+    Held { input: String },
+    ToggleOn { id: String },
+    // Variables are set by SetVariable and are unset until then
+    Variable { name: String, value: String },
+    // Times are "HH:MM", the range includes the start but not the end and wraps past midnight when the end is earlier
+    TimeBetween { start: String, end: String },
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+// What the conditions are tested against, taken when the If operation runs
+pub struct ConditionContext {
+    pub window_title: String,
+    pub app_name: String,
+    pub held: HashSet<Modifier>,
+    pub toggles: ToggleStates,
+    pub variables: HashMap<String, String>,
+    pub now: NaiveTime,
+}
+
+// Values set by SetVariable, kept until the app exits
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    pub values: HashMap<String, String>,
+}
+
+impl Condition {
+    // Invalid patterns and times are false, validate catches them when the config is loaded
+    pub fn evaluate(&self, context: &ConditionContext) -> bool {
+        match self {
+            Condition::WindowTitle { pattern } => is_match(pattern, &context.window_title),
+            Condition::AppName { pattern } => is_match(pattern, &context.app_name),
+            Condition::Held { input } => input
+                .parse::<Modifier>()
+                .map(|input| context.held.contains(&input))
+                .unwrap_or(false),
+            Condition::ToggleOn { id } => context.toggles.is_on(id),
+            Condition::Variable { name, value } => context.variables.get(name) == Some(value),
+            Condition::TimeBetween { start, end } => match (parse_time(start), parse_time(end)) {
+                (Ok(start), Ok(end)) if start <= end => start <= context.now && context.now < end,
+                (Ok(start), Ok(end)) => start <= context.now || context.now < end,
+                _ => false,
+            },
+            Condition::Not(condition) => !condition.evaluate(context),
+            Condition::All(conditions) => conditions.iter().all(|c| c.evaluate(context)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.evaluate(context)),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Condition::WindowTitle { pattern } | Condition::AppName { pattern } => {
+                Regex::new(pattern)?;
+            }
+            Condition::Held { input } => {
+                input
+                    .parse::<Modifier>()
+                    .map_err(|_| anyhow!("Unknown input {:?}", input))?;
+            }
+            Condition::TimeBetween { start, end } => {
+                parse_time(start)?;
+                parse_time(end)?;
+            }
+            Condition::Not(condition) => condition.validate()?,
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                for condition in conditions {
+                    condition.validate()?;
+                }
+            }
+            Condition::ToggleOn { .. } | Condition::Variable { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+fn is_match(pattern: &str, text: &str) -> bool {
+    Regex::new(pattern)
+        .map(|re| re.is_match(text))
+        .unwrap_or(false)
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| anyhow!("Invalid time {:?}, expected HH:MM", time))
+}
@@ -1,21 +1,321 @@
+use std::fmt;
 use std::fs;
+use std::str::FromStr;
 use std::collections::{HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use dirs::home_dir;
+use enigo::{Axis, Button, Key};
+use regex::Regex;
 
 use serde::{ser, de, Deserialize, Serialize};
 
+use crate::clipboard::TextTransform;
+use crate::condition::Condition;
+use crate::keys::parse_key;
+use crate::launcher::check_url;
+use crate::hid::PAD_BUTTON_COUNT;
+#[cfg(target_os = "linux")]
+use crate::keyboard::parse_key as parse_keyboard_key;
+use crate::macropad_state::{BUTTON_COUNT, ENCODER_COUNT};
+use crate::template::validate_template;
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
     pub application_profiles: HashMap<String, ApplicationProfile>,
+    #[serde(default)]
+    pub debounce: DebounceConfig,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    // Read from this serial port instead of the HID interface, takes effect on restart
+    pub serial: Option<SerialConfig>,
+    // MIDI control surfaces to use instead of the HID interface, takes effect on restart
+    #[serde(default)]
+    pub midi_devices: Vec<MidiDeviceConfig>,
+    // Keyboards used as extra pads alongside the pad, takes effect on restart
+    #[serde(default)]
+    pub keyboards: Vec<KeyboardConfig>,
+    #[serde(default)]
+    pub launch: LaunchConfig,
+    // Operations shared between profiles, run with CallMacro
+    #[serde(default)]
+    pub macros: HashMap<String, Vec<Operation>>,
+}
+
+impl AppConfig {
+    // Iterate over all profiles and match regex against window title
+    // First matching profile is taken
+    pub fn get_application_profile(&self, window_title: &str) -> Option<(&String, &ApplicationProfile)> {
+        self.application_profiles.iter().find(|(pattern, _)| {
+            Regex::new(pattern)
+                .map(|re| re.is_match(window_title))
+                .unwrap_or(false)
+        })
+    }
+
+    // Ids of the toggles whose `on` operations leave keys held, in any profile
+    pub fn get_toggles_holding_keys(&self) -> HashSet<String> {
+        self.application_profiles
+            .values()
+            .flat_map(|profile| profile.bindings.iter())
+            .filter_map(|(_, command)| command.toggle.as_ref())
+            .filter(|toggle| !held_keys(&toggle.on, &self.macros).is_empty())
+            .map(|toggle| toggle.id.clone())
+            .collect()
+    }
+
+    // Checks what deserializing can't, such as key names
+    // Configs with anything invalid are rejected, with every problem found
+    pub fn validate(&self) -> Result<()> {
+        let errors = self.clone().remove_invalid();
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+
+    // Drops the bindings, macros and device entries which are invalid, so one mistake doesn't stop the rest loading
+    // Returns why each was dropped
+    pub fn remove_invalid(&mut self) -> Vec<String> {
+        let mut errors = vec![];
+
+        for keyboard in self.keyboards.iter_mut() {
+            keyboard.keys.retain(|key, id| {
+                // Keyboards are only read on Linux, where keys are named as in evdev
+                #[cfg(target_os = "linux")]
+                if parse_keyboard_key(key).is_none() {
+                    errors.push(format!("Keyboard {:?}: unknown key {}", keyboard.device, key));
+                    return false;
+                }
+                if (*id as usize) < BUTTON_COUNT {
+                    return true;
+                }
+                errors.push(format!(
+                    "Keyboard {:?}: key {} has button id {}, ids must be below {}",
+                    keyboard.device, key, id, BUTTON_COUNT
+                ));
+                false
+            });
+        }
+
+        // A device on an impossible channel is dropped whole, rather than picking up every channel
+        self.midi_devices.retain(|midi_device| match midi_device.channel {
+            Some(channel) if !(1..=16).contains(&channel) => {
+                errors.push(format!(
+                    "MIDI device {:?}: channel {} must be from 1 to 16",
+                    midi_device.port, channel
+                ));
+                false
+            }
+            _ => true,
+        });
+        for midi_device in self.midi_devices.iter_mut() {
+            // Notes stand in for the pad's buttons, which come in the same reports
+            midi_device.notes.retain(|note, id| {
+                if (*id as usize) < PAD_BUTTON_COUNT {
+                    return true;
+                }
+                errors.push(format!(
+                    "MIDI device {:?}: note {} has button id {}, ids must be below {}",
+                    midi_device.port, note, id, PAD_BUTTON_COUNT
+                ));
+                false
+            });
+            midi_device.controllers.retain(|number, controller| {
+                if (controller.encoder as usize) < ENCODER_COUNT {
+                    return true;
+                }
+                errors.push(format!(
+                    "MIDI device {:?}: controller {} has encoder {}, encoders must be below {}",
+                    midi_device.port, number, controller.encoder, ENCODER_COUNT
+                ));
+                false
+            });
+        }
+
+        // Dropping a macro breaks the macros calling it, so this repeats until none are dropped
+        loop {
+            let invalid = self
+                .macros
+                .iter()
+                .filter_map(|(name, operations)| {
+                    operations
+                        .iter()
+                        .find_map(|operation| {
+                            operation
+                                .validate()
+                                .and_then(|_| {
+                                    check_macros_exist(&self.macros, operation.called_macros())
+                                })
+                                .err()
+                        })
+                        .map(|e| (name.clone(), e))
+                })
+                .collect::<Vec<_>>();
+            if invalid.is_empty() {
+                break;
+            }
+            for (name, e) in invalid {
+                errors.push(format!("Macro {:?}: {}", name, e));
+                self.macros.remove(&name);
+            }
+        }
+
+        for (pattern, profile) in self.application_profiles.iter_mut() {
+            profile.bindings.retain(|(_, command)| {
+                let result = command
+                    .validate()
+                    .and_then(|_| check_macros_exist(&self.macros, command.called_macros()));
+                match result {
+                    Ok(()) => true,
+                    Err(e) => {
+                        errors.push(format!(
+                            "Profile {:?}, command {:?}: {}",
+                            pattern, command.display_name, e
+                        ));
+                        false
+                    }
+                }
+            });
+        }
+
+        errors
+    }
+}
+
+fn check_macros_exist(macros: &HashMap<String, Vec<Operation>>, names: Vec<&str>) -> Result<()> {
+    for name in names {
+        if !macros.contains_key(name) {
+            bail!("Unknown macro {:?}", name);
+        }
+    }
+    Ok(())
+}
+
+// Windows are in milliseconds, 0 disables filtering
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DebounceConfig {
+    // Applies to buttons without their own window, and to encoder push switches
+    pub button_ms: u64,
+    // Button id to window
+    pub buttons: HashMap<u8, u64>,
+    // Encoder detents reversing the previous detent within this window are dropped
+    pub encoder_reversal_ms: u64,
+}
+
+// Limits on what Run and OpenPath operations may start
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LaunchConfig {
+    // Regexes matching the whole program or path, nothing is allowed when empty
+    pub allow_list: Vec<String>,
+    // Programs run with `wait` are killed after this long
+    pub timeout_ms: u64,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        LaunchConfig {
+            allow_list: vec![],
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+// For pads with a text display showing the profile and key labels
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DisplayConfig {
+    pub enabled: bool,
+    // Size of the display in characters
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        // 128x64 display with a 6x8 font
+        DisplayConfig {
+            enabled: false,
+            columns: 21,
+            rows: 8,
+        }
+    }
+}
+
+// For pads which expose a USB serial port instead of a vendor HID interface
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialConfig {
+    // e.g. "COM3" or "/dev/ttyACM0"
+    pub port: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+}
+
+fn default_baud_rate() -> u32 {
+    115_200
+}
+
+// Grabs a keyboard, e.g. a numpad, so its keys only act as buttons
+// Only supported on Linux
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyboardConfig {
+    // Regex matched against the input device name
+    pub device: String,
+    // Key name, e.g. "KEY_KP1", to button id
+    // Ids from 12 up don't clash with the pad's buttons
+    pub keys: HashMap<String, u8>,
+}
+
+// Maps a MIDI control surface onto buttons and encoders
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiDeviceConfig {
+    // Regex matched against the MIDI port name
+    pub port: String,
+    // 1 to 16, messages on any channel are used if not set
+    pub channel: Option<u8>,
+    // Note number to button id, below the pad's button count
+    #[serde(default)]
+    pub notes: HashMap<u8, u8>,
+    // Controller number to encoder
+    #[serde(default)]
+    pub controllers: HashMap<u8, MidiController>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiController {
+    pub encoder: u8,
+    #[serde(default)]
+    pub mode: MidiControllerMode,
+}
+
+// How controller values encode movement
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MidiControllerMode {
+    // Knobs and faders sending their position, movement is the change from the previous value
+    Absolute,
+    // Endless encoders sending 1 and up for increments and 127 and down for decrements
+    #[default]
+    Relative,
+    // Endless encoders sending 65 and up for increments and 63 and down for decrements
+    RelativeOffset,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ApplicationProfile {
     pub bindings: Vec<(KeyCombination, Command)>,
+    // Button id to LED
+    pub leds: Option<HashMap<u8, LedState>>,
+    // Modifiers, e.g. "BTN_4+ENC_0_PUSH", to the LEDs that change while they are held
+    pub layers: Option<HashMap<String, HashMap<u8, LedState>>>,
 }
 
 impl ApplicationProfile {
@@ -25,15 +325,106 @@ impl ApplicationProfile {
             .find(|(a, _)| a == key_combination)
             .map(|(_, b)| b.clone())
     }
+
+    // LEDs of toggles showing their state, by toggle id to whether it is on
+    pub fn get_toggle_leds(&self, toggles: &HashMap<String, bool>) -> HashMap<u8, LedState> {
+        self.bindings
+            .iter()
+            .filter_map(|(_, command)| command.toggle.as_ref())
+            .filter_map(|toggle| {
+                let led = toggle.led?;
+                let on = toggles.get(&toggle.id).copied().unwrap_or(false);
+                Some((led.button, if on { led.on } else { led.off }))
+            })
+            .collect()
+    }
+
+    // LEDs for the active modifiers
+    // Layers with more modifiers are applied over those with fewer
+    pub fn get_leds(&self, modifiers: &HashSet<Modifier>) -> HashMap<u8, LedState> {
+        let mut leds = self.leds.clone().unwrap_or_default();
+
+        if let Some(layers) = &self.layers {
+            let mut active_layers = layers
+                .iter()
+                .filter_map(|(layer_modifiers, layer)| {
+                    let layer_modifiers = parse_modifiers(layer_modifiers).ok()?;
+                    if layer_modifiers.is_subset(modifiers) {
+                        Some((layer_modifiers.len(), layer))
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            active_layers.sort_by_key(|(n, _)| *n);
+
+            for (_, layer) in active_layers {
+                leds.extend(layer.iter().map(|(id, led)| (*id, *led)));
+            }
+        }
+
+        leds
+    }
+}
+
+// Pads without RGB LEDs treat any colour as on
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum LedState {
+    #[default]
+    Off,
+    On,
+    Rgb { r: u8, g: u8, b: u8 },
 }
 
 #[derive(Debug, Clone)]
 pub struct KeyCombination {
-    // Button ids
-    pub modifiers: Option<HashSet<u8>>,
+    pub modifiers: Option<HashSet<Modifier>>,
     pub action: Action,
 }
 
+// Held or recently turned inputs which must be active alongside the action
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum Modifier {
+    Button(u8),
+    EncoderPush(u8),
+    // The encoder is mid-rotation in the given direction
+    EncoderIncrement(u8),
+    EncoderDecrement(u8),
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Modifier::Button(id) => write!(f, "BTN_{}", id),
+            Modifier::EncoderPush(id) => write!(f, "ENC_{}_PUSH", id),
+            Modifier::EncoderIncrement(id) => write!(f, "ENC_{}_INC", id),
+            Modifier::EncoderDecrement(id) => write!(f, "ENC_{}_DEC", id),
+        }
+    }
+}
+
+impl FromStr for Modifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_id = |x: &str| x.parse::<u8>().map_err(|e| e.to_string());
+
+        match s.split("_").collect::<Vec<&str>>()[..] {
+            ["BTN", x] => Ok(Modifier::Button(parse_id(x)?)),
+            ["ENC", x, "PUSH"] => Ok(Modifier::EncoderPush(parse_id(x)?)),
+            ["ENC", x, "INC"] => Ok(Modifier::EncoderIncrement(parse_id(x)?)),
+            ["ENC", x, "DEC"] => Ok(Modifier::EncoderDecrement(parse_id(x)?)),
+            _ => Err(format!("Invalid key: {}", s)),
+        }
+    }
+}
+
+// Parses modifiers joined by "+", e.g. "BTN_4+ENC_0_PUSH"
+pub fn parse_modifiers(s: &str) -> Result<HashSet<Modifier>, String> {
+    s.split("+").map(Modifier::from_str).collect()
+}
+
 impl Serialize for KeyCombination {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -42,17 +433,18 @@ impl Serialize for KeyCombination {
         let mut s = "".to_string();
 
         if let Some(modifiers) = &self.modifiers {
-            let mut m = modifiers.clone().into_iter().collect::<Vec<u8>>();
+            let mut m = modifiers.iter().collect::<Vec<&Modifier>>();
             m.sort();
-            let modifiers = m.iter().map(|x| format!("BTN_{}+", x)).collect::<Vec<String>>().join("");
+            let modifiers = m.iter().map(|x| format!("{}+", x)).collect::<Vec<String>>().join("");
             s = modifiers;
         }
 
         match self.action {
-            Action::ButtonPress { id } => return serializer.serialize_str(&format!("{}BTN_{}", s, id)),
-            Action::EncoderDecrement { id } => return serializer.serialize_str(&format!("{}ENC_{}_DEC", s, id)),
-            Action::EncoderIncrement { id } => return serializer.serialize_str(&format!("{}ENC_{}_INC", s, id)),
-            _ => return Err(ser::Error::custom("Invalid action")) 
+            Action::ButtonPress { id } => serializer.serialize_str(&format!("{}BTN_{}", s, id)),
+            Action::EncoderPush { id } => serializer.serialize_str(&format!("{}ENC_{}_PUSH", s, id)),
+            Action::EncoderDecrement { id } => serializer.serialize_str(&format!("{}ENC_{}_DEC", s, id)),
+            Action::EncoderIncrement { id } => serializer.serialize_str(&format!("{}ENC_{}_INC", s, id)),
+            _ => Err(ser::Error::custom("Invalid action"))
         }
     }
 }
@@ -66,10 +458,13 @@ impl<'de> Deserialize<'de> for KeyCombination {
         let keys = s.split("+").collect::<Vec<&str>>();
         let n = keys.len();
 
+        let parse_id = |x: &str| x.parse::<u8>().map_err(de::Error::custom);
+
         let mut c = match keys[n - 1].split("_").collect::<Vec<&str>>()[..] {
-            ["BTN", x] => KeyCombination { modifiers: None, action: Action::ButtonPress { id: x.parse().unwrap() } },
-            ["ENC", x, "DEC"] => KeyCombination { modifiers: None, action: Action::EncoderDecrement { id: x.parse().unwrap() } },
-            ["ENC", x, "INC"] => KeyCombination { modifiers: None, action: Action::EncoderIncrement { id: x.parse().unwrap() } }, 
+            ["BTN", x] => KeyCombination { modifiers: None, action: Action::ButtonPress { id: parse_id(x)? } },
+            ["ENC", x, "PUSH"] => KeyCombination { modifiers: None, action: Action::EncoderPush { id: parse_id(x)? } },
+            ["ENC", x, "DEC"] => KeyCombination { modifiers: None, action: Action::EncoderDecrement { id: parse_id(x)? } },
+            ["ENC", x, "INC"] => KeyCombination { modifiers: None, action: Action::EncoderIncrement { id: parse_id(x)? } },
             _ => return Err(de::Error::custom("Invalid action")),
         };
 
@@ -79,11 +474,7 @@ impl<'de> Deserialize<'de> for KeyCombination {
 
         let mut modifiers = HashSet::new();
         for k in keys.iter().take(n - 1) {
-            let id = match k.split("_").collect::<Vec<&str>>()[..] {
-                ["BTN", x] => x.parse().unwrap(),
-                _ => return Err(de::Error::custom("Invalid key")),
-            };
-            modifiers.insert(id);
+            modifiers.insert(k.parse::<Modifier>().map_err(de::Error::custom)?);
         }
 
         c.modifiers = Some(modifiers);
@@ -103,23 +494,155 @@ impl Eq for KeyCombination {}
 #[serde(rename_all = "camelCase")]
 pub enum Action {
     ButtonPress { id: u8 },
+    EncoderPush { id: u8 },
     EncoderDecrement { id: u8 },
     EncoderIncrement { id: u8 },
     // Not for use in config
     #[default]
     None,
     ButtonRelease { id: u8 },
+    EncoderRelease { id: u8 },
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Command {
     pub display_name: String,
     pub operations: Option<Vec<Operation>>,
     pub radial_menu_items: Option<Vec<RadialMenuItem>>,
+    // Only applies to encoder bindings
+    pub acceleration: Option<Vec<AccelerationStep>>,
+    #[serde(default)]
+    pub concurrency: ConcurrencyPolicy,
+    // Used instead of the operations when set
+    pub toggle: Option<Toggle>,
+    // Used instead of the operations when set
+    pub cycle: Option<Cycle>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+// Alternates between running `on` and `off` on each press
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Toggle {
+    // Commands with the same id share their state
+    pub id: String,
+    pub on: Vec<Operation>,
+    pub off: Vec<Operation>,
+    // Shows the state on a button's LED
+    pub led: Option<ToggleLed>,
+}
+
+// Runs the next list of operations on each press, wrapping around at the end
+// Encoder decrements step backwards
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Cycle {
+    // Commands with the same id share their position
+    pub id: String,
+    pub steps: Vec<Vec<Operation>>,
+    // Starts over from the first step after this long without a press
+    pub reset_after_ms: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToggleLed {
+    pub button: u8,
+    pub on: LedState,
+    #[serde(default)]
+    pub off: LedState,
+}
+
+// What happens when a command is triggered while it is still running or waiting to run
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConcurrencyPolicy {
+    // The new trigger is dropped
+    Ignore,
+    // The running command is cancelled and started again
+    Restart,
+    // Runs after everything queued before it
+    #[default]
+    Queue,
+    // Runs straight away on its own thread
+    Parallel,
+}
+
+impl Command {
+    // Number of times the operations should run for an encoder turned by `steps` detents
+    // `speed` is in detents per second, and is unknown for the first turn
+    pub fn get_repetitions(&self, steps: u8, speed: Option<f64>) -> u64 {
+        let multiplier = match (&self.acceleration, speed) {
+            (Some(acceleration), Some(speed)) => acceleration
+                .iter()
+                .filter(|step| speed >= step.min_speed)
+                .max_by(|a, b| a.min_speed.total_cmp(&b.min_speed))
+                .map(|step| step.multiplier)
+                .unwrap_or(1),
+            _ => 1,
+        };
+
+        steps as u64 * multiplier
+    }
+
+    // Operations of the command itself, its toggle and its cycle, without radial menu items
+    fn own_operations(&self) -> impl Iterator<Item = &Operation> {
+        let toggle_operations = self
+            .toggle
+            .iter()
+            .flat_map(|toggle| toggle.on.iter().chain(toggle.off.iter()));
+        let cycle_operations = self
+            .cycle
+            .iter()
+            .flat_map(|cycle| cycle.steps.iter().flatten());
+        self.operations
+            .iter()
+            .flatten()
+            .chain(toggle_operations)
+            .chain(cycle_operations)
+    }
+
+    // Names of the macros called by the operations and radial menu items
+    pub fn called_macros(&self) -> Vec<&str> {
+        let mut names = self
+            .own_operations()
+            .flat_map(Operation::called_macros)
+            .collect::<Vec<&str>>();
+        for item in self.radial_menu_items.iter().flatten() {
+            names.extend(item.command.called_macros());
+        }
+        names
+    }
+
+    // Whether running the command may abort macros, including through the macros it calls
+    pub fn contains_abort(&self, macros: &HashMap<String, Vec<Operation>>) -> bool {
+        let mut visited = HashSet::new();
+        self.own_operations()
+            .any(|operation| operation.contains_abort(macros, &mut visited))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for operation in self.own_operations() {
+            operation.validate()?;
+        }
+        for item in self.radial_menu_items.iter().flatten() {
+            item.command
+                .validate()
+                .map_err(|e| anyhow!("Radial menu item {:?}: {}", item.label, e))?;
+        }
+        Ok(())
+    }
+}
+
+// Once the encoder is spun at `min_speed` detents per second or faster, each detent runs the operations `multiplier` times
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccelerationStep {
+    pub min_speed: f64,
+    pub multiplier: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum Operation {
     KeyPress { key: String },
@@ -129,13 +652,279 @@ pub enum Operation {
         times: u64, 
         operations: Vec<Operation>
     },
+    // Types the text as it is, with placeholders such as {date} and {window} filled in
+    TypeText { text: String },
+    // Taps the last key while holding the others, e.g. "Ctrl+Shift+T"
+    Shortcut { keys: String },
+    MouseClick {
+        #[serde(default)]
+        button: MouseButton,
+    },
+    // Relative moves are from the current position, otherwise to a position on the screen
+    MouseMove {
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        relative: bool,
+    },
+    // Holds the button down while moving as for MouseMove
+    MouseDrag {
+        #[serde(default)]
+        button: MouseButton,
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        relative: bool,
+    },
+    // In wheel notches, positive amounts scroll down or right
+    Scroll {
+        #[serde(default)]
+        axis: ScrollAxis,
+        amount: i32,
+    },
+    // Programs must be in the launch allow list
+    // With `wait` the macro continues once the program exits, and its output is logged
+    Run {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        cwd: Option<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+        #[serde(default)]
+        wait: bool,
+    },
+    // Opens an http, https or mailto link in the default app
+    OpenUrl { url: String },
+    // Opens a file or folder in the default app, paths must be in the launch allow list
+    OpenPath { path: String },
+    // Paste with a Shortcut "Ctrl+V" afterwards, and a short Delay before restoring so the app has read it
+    ClipboardSet { text: String },
+    ClipboardSave {},
+    // Puts back what the last ClipboardSave saved
+    ClipboardRestore {},
+    // Transforms the text on the clipboard in place
+    ClipboardTransform { kind: TextTransform },
+    // Runs the operations of a macro from the config
+    CallMacro { name: String },
+    // Cancels every running and queued command, and releases the keys they held
+    // Commands with it always run in parallel so they aren't stuck behind what they are stopping
+    AbortMacros {},
+    // Runs `then` if the condition holds when it is reached, otherwise `else`
+    If {
+        condition: Condition,
+        then: Vec<Operation>,
+        #[serde(default, rename = "else")]
+        otherwise: Vec<Operation>,
+    },
+    // Sets a variable for If conditions to test
+    SetVariable { name: String, value: String },
     // Not for use in config
     #[default]
     None,
     KeyRelease { key: String },
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Middle,
+    Right,
+    Back,
+    Forward,
+}
+
+impl From<MouseButton> for Button {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => Button::Left,
+            MouseButton::Middle => Button::Middle,
+            MouseButton::Right => Button::Right,
+            MouseButton::Back => Button::Back,
+            MouseButton::Forward => Button::Forward,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ScrollAxis {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+impl From<ScrollAxis> for Axis {
+    fn from(axis: ScrollAxis) -> Self {
+        match axis {
+            ScrollAxis::Vertical => Axis::Vertical,
+            ScrollAxis::Horizontal => Axis::Horizontal,
+        }
+    }
+}
+
+// Keys of a shortcut in the order they are pressed, the last being the one tapped
+// "+" itself is written as "Ctrl++"
+pub fn parse_shortcut(keys: &str) -> Result<Vec<Key>> {
+    let mut names = keys.split('+').map(str::trim).collect::<Vec<&str>>();
+    if names.len() >= 2 && names[names.len() - 2..] == ["", ""] {
+        names.truncate(names.len() - 2);
+        names.push("+");
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            parse_key(name).ok_or_else(|| anyhow!("Unknown key {:?} in shortcut {:?}", name, keys))
+        })
+        .collect()
+}
+
+// Macros can call other macros this many levels deep
+pub const MAX_MACRO_DEPTH: usize = 16;
+
+// Keys pressed by KeyPress and not released by a later KeyRelease, last pressed first
+// Looks inside Repeat, If and the macros called, as deep as they can run
+pub fn held_keys<'a>(
+    operations: &'a [Operation],
+    macros: &'a HashMap<String, Vec<Operation>>,
+) -> Vec<&'a str> {
+    let mut keys = vec![];
+    collect_held_keys(operations, macros, 0, &mut HashSet::new(), &mut keys);
+    keys
+}
+
+// `released_keys` are released by operations after these ones
+fn collect_held_keys<'a>(
+    operations: &'a [Operation],
+    macros: &'a HashMap<String, Vec<Operation>>,
+    depth: usize,
+    released_keys: &mut HashSet<&'a str>,
+    keys: &mut Vec<&'a str>,
+) {
+    for operation in operations.iter().rev() {
+        match operation {
+            Operation::KeyRelease { key } => {
+                released_keys.insert(key.as_str());
+            }
+            Operation::KeyPress { key } => {
+                if released_keys.remove(key.as_str()) || keys.contains(&key.as_str()) {
+                    continue;
+                }
+                keys.push(key.as_str());
+            }
+            Operation::Repeat { operations, .. } => {
+                collect_held_keys(operations, macros, depth, released_keys, keys);
+            }
+            // Either branch may have run, so keys only count as released if both release them
+            Operation::If {
+                then, otherwise, ..
+            } => {
+                let mut then_released_keys = released_keys.clone();
+                collect_held_keys(then, macros, depth, &mut then_released_keys, keys);
+                collect_held_keys(otherwise, macros, depth, released_keys, keys);
+                released_keys.retain(|key| then_released_keys.contains(key));
+            }
+            Operation::CallMacro { name } if depth < MAX_MACRO_DEPTH => {
+                if let Some(operations) = macros.get(name) {
+                    collect_held_keys(operations, macros, depth + 1, released_keys, keys);
+                }
+            }
+            // Nothing else is left held, shortcuts release what they press straight away and leave keys held by KeyPress alone
+            _ => {}
+        }
+    }
+}
+
+impl Operation {
+    pub fn called_macros(&self) -> Vec<&str> {
+        match self {
+            Operation::CallMacro { name } => vec![name.as_str()],
+            Operation::Repeat { operations, .. } => operations
+                .iter()
+                .flat_map(Operation::called_macros)
+                .collect(),
+            Operation::If {
+                then, otherwise, ..
+            } => then
+                .iter()
+                .chain(otherwise.iter())
+                .flat_map(Operation::called_macros)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // Macros already looked through are in `visited`, so macros calling each other don't loop
+    pub fn contains_abort<'a>(
+        &'a self,
+        macros: &'a HashMap<String, Vec<Operation>>,
+        visited: &mut HashSet<&'a str>,
+    ) -> bool {
+        match self {
+            Operation::AbortMacros {} => true,
+            Operation::CallMacro { name } => {
+                visited.insert(name.as_str())
+                    && macros.get(name).is_some_and(|operations| {
+                        operations
+                            .iter()
+                            .any(|operation| operation.contains_abort(macros, visited))
+                    })
+            }
+            Operation::Repeat { operations, .. } => operations
+                .iter()
+                .any(|operation| operation.contains_abort(macros, visited)),
+            Operation::If {
+                then, otherwise, ..
+            } => then
+                .iter()
+                .chain(otherwise.iter())
+                .any(|operation| operation.contains_abort(macros, visited)),
+            _ => false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Operation::KeyPress { key } | Operation::KeyTap { key } | Operation::KeyRelease { key }
+                if parse_key(key).is_none() =>
+            {
+                bail!("Unknown key {:?}", key);
+            }
+            Operation::Shortcut { keys } => {
+                parse_shortcut(keys)?;
+            }
+            Operation::TypeText { text } => validate_template(text)?,
+            Operation::OpenUrl { url } => check_url(url)?,
+            Operation::ClipboardTransform {
+                kind: TextTransform::RegexReplace { pattern, .. },
+            } => {
+                Regex::new(pattern)?;
+            }
+            Operation::Repeat { operations, .. } => {
+                for operation in operations {
+                    operation.validate()?;
+                }
+            }
+            Operation::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                condition.validate()?;
+                for operation in then.iter().chain(otherwise.iter()) {
+                    operation.validate()?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct RadialMenuItem {
     pub label: String,
@@ -152,7 +941,7 @@ pub fn get_config_path() -> std::path::PathBuf {
 pub fn load_config() -> Result<AppConfig> {
     let config_path = get_config_path();
     dbg!(&config_path);
-    if !fs::metadata(&config_path).is_ok() {
+    if fs::metadata(&config_path).is_err() {
         // Create directory if it doesn't exist
         fs::create_dir_all(config_path.parent().unwrap())?;
         // Create config file
@@ -161,5 +950,34 @@ pub fn load_config() -> Result<AppConfig> {
         fs::write(&config_path, serde_json::to_string(&AppConfig::default()).unwrap()).unwrap();
     }
     let config = std::fs::read_to_string(config_path)?;
-    Ok(serde_json::from_str(&config)?)
+    let config: AppConfig = serde_json::from_str(&config)?;
+    Ok(config)
+}
+
+// The config as it is written, along with why any parts of it are skipped when used
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    pub errors: Vec<String>,
+}
+
+impl LoadedConfig {
+    // The config with the invalid parts removed
+    pub fn usable(&self) -> AppConfig {
+        let mut config = self.config.clone();
+        config.remove_invalid();
+        config
+    }
+}
+
+// Invalid bindings, macros and device entries don't stop the config loading, they are reported and skipped
+// Files which can't be parsed at all are an error, and are never replaced
+pub fn load_validated_config() -> Result<LoadedConfig> {
+    let config = load_config()?;
+    let errors = config.clone().remove_invalid();
+    for e in errors.iter() {
+        eprintln!("Skipping invalid config: {}", e);
+    }
+    Ok(LoadedConfig { config, errors })
 }
@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use crate::config::DebounceConfig;
+use crate::macropad_state::EncoderTurn;
+
+// Bits of the first two report bytes holding buttons and encoder push switches
+// The remaining 2 bits are the 2 bit encoder value
+const BUTTON_BITS: u16 = 0b1100_1111_1111_1111;
+
+// Sits between the device and handle_report, holding back button chatter and encoder glitches
+// Reports are passed through unchanged when the config has no windows set
+pub struct ReportFilter {
+    config: DebounceConfig,
+    // Whether the firmware sends step counts, as for handle_report_at
+    step_counts: Option<bool>,
+    // Last report passed on
+    last_report: Option<Vec<u8>>,
+    // Latest button bits read from the device, some of which may still be waiting out their window
+    raw_buttons: u16,
+    // When each button bit last changed in a report that was passed on
+    changed_at: [Option<Instant>; 16],
+    // Last detent passed on for each encoder
+    encoder_turns: Vec<Option<EncoderTurn>>,
+}
+
+impl ReportFilter {
+    pub fn new(config: DebounceConfig, step_counts: Option<bool>) -> Self {
+        ReportFilter {
+            config,
+            step_counts,
+            last_report: None,
+            raw_buttons: 0,
+            changed_at: [None; 16],
+            encoder_turns: vec![],
+        }
+    }
+
+    // Filters a report read from the device
+    // Returns None if nothing is left to pass on
+    pub fn filter(&mut self, report: &[u8], now: Instant) -> Option<Vec<u8>> {
+        if report.len() < 2 {
+            // Leave it to handle_report to reject
+            return Some(report.to_vec());
+        }
+
+        self.raw_buttons = u16::from_le_bytes([report[0], report[1]]) & BUTTON_BITS;
+        let buttons = self.debounce_buttons(now);
+
+        let mut filtered = report.to_vec();
+        let step_counts = self.step_counts.unwrap_or(report.len() > 2);
+        if step_counts {
+            for (id, step_count) in filtered.iter_mut().skip(2).enumerate() {
+                let direction = (*step_count as i8).signum();
+                if direction != 0 && !self.accept_turn(id, direction, now) {
+                    *step_count = 0;
+                }
+            }
+        } else {
+            let previous = self
+                .last_report
+                .as_ref()
+                .map(|last| (last[1] >> 4) & 0b11)
+                .unwrap_or(0);
+            let direction = match (report[1] >> 4) & 0b11 {
+                0b01 => 1,
+                0b11 => -1,
+                _ => 0,
+            };
+            // Only movements away from rest are detents
+            if previous == 0 && direction != 0 && !self.accept_turn(0, direction, now) {
+                filtered[1] &= !(0b11 << 4);
+            }
+        }
+        set_buttons(&mut filtered, buttons);
+
+        let moved = step_counts && filtered[2..].iter().any(|x| *x != 0);
+        let changed = match &self.last_report {
+            Some(last) => last[..2] != filtered[..2],
+            None => true,
+        };
+        if !moved && !changed {
+            return None;
+        }
+
+        self.last_report = Some(filtered.clone());
+        Some(filtered)
+    }
+
+    // Passes on button changes which were held back and have since settled
+    // Needs to be called regularly as the device won't send another report until something else changes
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let mut report = self.last_report.clone()?;
+
+        let buttons = self.debounce_buttons(now);
+        if buttons == self.last_buttons() {
+            return None;
+        }
+
+        set_buttons(&mut report, buttons);
+        // Movement was already passed on with the last report
+        for byte in report.iter_mut().skip(2) {
+            *byte = 0;
+        }
+
+        self.last_report = Some(report.clone());
+        Some(report)
+    }
+
+    fn last_buttons(&self) -> u16 {
+        self.last_report
+            .as_ref()
+            .map(|last| u16::from_le_bytes([last[0], last[1]]) & BUTTON_BITS)
+            .unwrap_or(0)
+    }
+
+    fn window(&self, bit: usize) -> Duration {
+        let ms = if bit < 12 {
+            self.config
+                .buttons
+                .get(&(bit as u8))
+                .copied()
+                .unwrap_or(self.config.button_ms)
+        } else {
+            self.config.button_ms
+        };
+        Duration::from_millis(ms)
+    }
+
+    // Button bits as they should be passed on at `now`
+    // A button may only change once per window, changes within the window are chatter
+    fn debounce_buttons(&mut self, now: Instant) -> u16 {
+        let mut buttons = self.last_buttons();
+
+        for bit in 0..16 {
+            let mask = 1 << bit;
+            if (self.raw_buttons ^ buttons) & mask == 0 {
+                continue;
+            }
+
+            let settled = match self.changed_at[bit] {
+                Some(changed_at) => now.duration_since(changed_at) >= self.window(bit),
+                None => true,
+            };
+            if settled {
+                buttons ^= mask;
+                self.changed_at[bit] = Some(now);
+            }
+        }
+
+        buttons
+    }
+
+    // Drops detents which reverse the direction of the previous detent too quickly to be intentional
+    fn accept_turn(&mut self, id: usize, direction: i8, now: Instant) -> bool {
+        if self.encoder_turns.len() <= id {
+            self.encoder_turns.resize(id + 1, None);
+        }
+
+        let window = Duration::from_millis(self.config.encoder_reversal_ms);
+        if let Some(turn) = self.encoder_turns[id] {
+            if turn.direction != direction && now.duration_since(turn.at) < window {
+                println!("Dropping encoder {} glitch", id);
+                return false;
+            }
+        }
+
+        self.encoder_turns[id] = Some(EncoderTurn { at: now, direction });
+        true
+    }
+}
+
+fn set_buttons(report: &mut [u8], buttons: u16) {
+    let other_bits = u16::from_le_bytes([report[0], report[1]]) & !BUTTON_BITS;
+    let bytes = ((buttons & BUTTON_BITS) | other_bits).to_le_bytes();
+    report[0] = bytes[0];
+    report[1] = bytes[1];
+}
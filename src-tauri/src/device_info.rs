@@ -0,0 +1,122 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::hid::PAD_BUTTON_COUNT;
+use crate::macropad_state::MacropadState;
+
+// Feature report describing the firmware
+// [FIRMWARE_INFO_REPORT_ID, major, minor, patch, buttons, encoders, columns, features]
+// Firmware older than 1.0.0 doesn't have it
+pub const FIRMWARE_INFO_REPORT_ID: u8 = 0x04;
+const FIRMWARE_INFO_LENGTH: usize = 8;
+
+// Bits of the features byte
+pub const FEATURE_RGB_LEDS: u8 = 1 << 0;
+pub const FEATURE_DISPLAY: u8 = 1 << 1;
+pub const FEATURE_STEP_COUNTS: u8 = 1 << 2;
+
+pub const MIN_FIRMWARE_VERSION: FirmwareVersion = FirmwareVersion {
+    major: 1,
+    minor: 0,
+    patch: 0,
+};
+
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    // None if the firmware couldn't be queried
+    pub firmware: Option<FirmwareInfo>,
+    // Whether the app can drive the firmware and its layout
+    pub supported: bool,
+}
+
+impl DeviceInfo {
+    pub fn new(
+        manufacturer: Option<String>,
+        product: Option<String>,
+        serial_number: Option<String>,
+        firmware: Option<FirmwareInfo>,
+    ) -> Self {
+        let supported = firmware
+            .as_ref()
+            .map(|firmware| firmware.is_supported())
+            .unwrap_or(false);
+
+        DeviceInfo {
+            manufacturer,
+            product,
+            serial_number,
+            firmware,
+            supported,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareInfo {
+    pub version: FirmwareVersion,
+    pub layout: Layout,
+}
+
+impl FirmwareInfo {
+    pub fn is_supported(&self) -> bool {
+        let macropad_state = MacropadState::default();
+
+        self.version >= MIN_FIRMWARE_VERSION
+            && self.layout.buttons as usize <= PAD_BUTTON_COUNT
+            && self.layout.encoders as usize <= macropad_state.encoders.len()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Layout {
+    pub buttons: u8,
+    pub encoders: u8,
+    // Buttons are numbered along rows of this many keys
+    pub columns: u8,
+    pub rgb_leds: bool,
+    pub display: bool,
+    // Whether the encoders report step counts rather than the 2 bit value
+    // None if the device can send either
+    pub step_counts: Option<bool>,
+}
+
+// Decodes the firmware info feature report, including its report id
+pub fn parse_firmware_info(report: &[u8]) -> Result<FirmwareInfo> {
+    if report.len() < FIRMWARE_INFO_LENGTH {
+        bail!("Firmware info report too short: {:?}", report);
+    }
+    if report[0] != FIRMWARE_INFO_REPORT_ID {
+        bail!("Unexpected report id: 0x{:02x}", report[0]);
+    }
+
+    let features = report[7];
+    Ok(FirmwareInfo {
+        version: FirmwareVersion {
+            major: report[1],
+            minor: report[2],
+            patch: report[3],
+        },
+        layout: Layout {
+            buttons: report[4],
+            encoders: report[5],
+            columns: report[6],
+            rgb_leds: features & FEATURE_RGB_LEDS != 0,
+            display: features & FEATURE_DISPLAY != 0,
+            step_counts: Some(features & FEATURE_STEP_COUNTS != 0),
+        },
+    })
+}
@@ -0,0 +1,344 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use rusb::{Direction, GlobalContext, Recipient, RequestType};
+use tauri::{Emitter, Manager};
+
+use crate::device_info::DeviceInfo;
+use crate::events::{FirmwareUpdateProgress, FirmwareUpdateStage};
+use crate::hid::{PRODUCT_ID, VENDOR_ID};
+use crate::output::OutputQueue;
+
+// Output report asking the firmware to reboot into its DFU bootloader
+// [BOOTLOADER_REPORT_ID, 'D', 'F', 'U']
+pub const BOOTLOADER_REPORT_ID: u8 = 0x05;
+pub const BOOTLOADER_MAGIC: &[u8] = b"DFU";
+
+// The bootloader enumerates with its own product id
+pub const DFU_PRODUCT_ID: u16 = 0x0002;
+const BOOTLOADER_TIMEOUT: Duration = Duration::from_secs(10);
+const USB_TIMEOUT: Duration = Duration::from_secs(5);
+// Interface class and subclass of DFU interfaces
+const DFU_CLASS: u8 = 0xFE;
+const DFU_SUBCLASS: u8 = 0x01;
+const DFU_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+// Used if the functional descriptor can't be found
+const DEFAULT_TRANSFER_SIZE: usize = 64;
+
+// DFU 1.1 class requests
+pub const DFU_DNLOAD: u8 = 1;
+pub const DFU_GETSTATUS: u8 = 3;
+pub const DFU_CLRSTATUS: u8 = 4;
+
+pub const DFU_STATUS_OK: u8 = 0;
+pub const DFU_STATE_IDLE: u8 = 2;
+pub const DFU_STATE_DNLOAD_IDLE: u8 = 5;
+pub const DFU_STATE_MANIFEST_WAIT_RESET: u8 = 8;
+pub const DFU_STATE_ERROR: u8 = 10;
+// A busy device is polled for at most this many times before giving up
+const MAX_POLLS: usize = 1000;
+
+// Images end with a DFU suffix
+// [bcdDevice, idProduct, idVendor, bcdDFU, 'U', 'F', 'D', bLength, dwCRC], all little endian
+pub const DFU_SUFFIX_LENGTH: usize = 16;
+
+// Class requests to the DFU interface, so the download can be tested without a device
+pub trait DfuTransport {
+    fn control_out(&mut self, request: u8, value: u16, data: &[u8]) -> Result<usize>;
+    fn control_in(&mut self, request: u8, value: u16, buf: &mut [u8]) -> Result<usize>;
+}
+
+pub struct RusbTransport {
+    handle: rusb::DeviceHandle<GlobalContext>,
+    interface: u8,
+}
+
+impl DfuTransport for RusbTransport {
+    fn control_out(&mut self, request: u8, value: u16, data: &[u8]) -> Result<usize> {
+        let request_type =
+            rusb::request_type(Direction::Out, RequestType::Class, Recipient::Interface);
+        Ok(self.handle.write_control(
+            request_type,
+            request,
+            value,
+            self.interface as u16,
+            data,
+            USB_TIMEOUT,
+        )?)
+    }
+
+    fn control_in(&mut self, request: u8, value: u16, buf: &mut [u8]) -> Result<usize> {
+        let request_type =
+            rusb::request_type(Direction::In, RequestType::Class, Recipient::Interface);
+        Ok(self.handle.read_control(
+            request_type,
+            request,
+            value,
+            self.interface as u16,
+            buf,
+            USB_TIMEOUT,
+        )?)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DfuStatus {
+    pub status: u8,
+    pub poll_timeout: Duration,
+    pub state: u8,
+}
+
+// Checks the DFU suffix and CRC of an image built for this pad
+// Returns the firmware without the suffix
+pub fn verify_image(image: &[u8], vendor_id: u16, product_id: u16) -> Result<&[u8]> {
+    if image.len() < DFU_SUFFIX_LENGTH {
+        bail!("Image too short to have a DFU suffix");
+    }
+
+    let suffix = &image[image.len() - DFU_SUFFIX_LENGTH..];
+    if &suffix[8..11] != b"UFD" || suffix[11] as usize != DFU_SUFFIX_LENGTH {
+        bail!("Image has no DFU suffix");
+    }
+
+    let crc = u32::from_le_bytes([suffix[12], suffix[13], suffix[14], suffix[15]]);
+    let expected_crc = dfu_crc(&image[..image.len() - 4]);
+    if crc != expected_crc {
+        bail!(
+            "Image CRC mismatch: 0x{:08x}, expected 0x{:08x}",
+            crc,
+            expected_crc
+        );
+    }
+
+    // 0xFFFF matches any device
+    let image_product_id = u16::from_le_bytes([suffix[2], suffix[3]]);
+    let image_vendor_id = u16::from_le_bytes([suffix[4], suffix[5]]);
+    if (image_vendor_id != 0xFFFF && image_vendor_id != vendor_id)
+        || (image_product_id != 0xFFFF && image_product_id != product_id)
+    {
+        bail!(
+            "Image is for VID: 0x{:04x}, PID: 0x{:04x}",
+            image_vendor_id,
+            image_product_id
+        );
+    }
+
+    Ok(&image[..image.len() - DFU_SUFFIX_LENGTH])
+}
+
+// CRC-32 as used by DFU suffixes, without the final inversion
+pub fn dfu_crc(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+pub fn get_status(transport: &mut impl DfuTransport) -> Result<DfuStatus> {
+    let mut buf = [0u8; 6];
+    let n_bytes = transport.control_in(DFU_GETSTATUS, 0, &mut buf)?;
+    if n_bytes < buf.len() {
+        bail!("DFU status too short: {:?}", &buf[..n_bytes]);
+    }
+
+    Ok(DfuStatus {
+        status: buf[0],
+        poll_timeout: Duration::from_millis(u32::from_le_bytes([buf[1], buf[2], buf[3], 0]) as u64),
+        state: buf[4],
+    })
+}
+
+// Polls the device until it reaches one of `states`, failing if it reports an error
+fn wait_for_state(transport: &mut impl DfuTransport, states: &[u8]) -> Result<DfuStatus> {
+    for _ in 0..MAX_POLLS {
+        let status = get_status(transport)?;
+        if status.status != DFU_STATUS_OK {
+            bail!(
+                "DFU error: status {}, state {}",
+                status.status,
+                status.state
+            );
+        }
+        if states.contains(&status.state) {
+            return Ok(status);
+        }
+        std::thread::sleep(status.poll_timeout);
+    }
+
+    Err(anyhow!("Timed out waiting for DFU state {:?}", states))
+}
+
+// Downloads the firmware in blocks of `transfer_size`, calling `progress` with the bytes written after each block
+pub fn download(
+    transport: &mut impl DfuTransport,
+    firmware: &[u8],
+    transfer_size: usize,
+    mut progress: impl FnMut(usize),
+) -> Result<()> {
+    if transfer_size == 0 {
+        bail!("DFU transfer size must be at least 1 byte");
+    }
+
+    // A previous attempt may have left the device in an error state
+    if get_status(transport)?.state == DFU_STATE_ERROR {
+        transport.control_out(DFU_CLRSTATUS, 0, &[])?;
+    }
+    wait_for_state(transport, &[DFU_STATE_IDLE])?;
+
+    let mut bytes_written = 0;
+    let blocks = firmware.chunks(transfer_size).collect::<Vec<&[u8]>>();
+    for (block, chunk) in blocks.iter().enumerate() {
+        transport.control_out(DFU_DNLOAD, block as u16, chunk)?;
+        wait_for_state(transport, &[DFU_STATE_DNLOAD_IDLE])?;
+
+        bytes_written += chunk.len();
+        progress(bytes_written);
+    }
+
+    // An empty block ends the download and has the device write the firmware
+    transport.control_out(DFU_DNLOAD, blocks.len() as u16, &[])?;
+    match wait_for_state(transport, &[DFU_STATE_IDLE, DFU_STATE_MANIFEST_WAIT_RESET]) {
+        Ok(_) => Ok(()),
+        // Devices may reset as soon as they are done, without answering
+        // Any other error means the firmware may not have been written
+        Err(e)
+            if matches!(
+                e.downcast_ref::<rusb::Error>(),
+                Some(rusb::Error::NoDevice | rusb::Error::Io)
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// wTransferSize is at offset 5 of the functional descriptor
+// A size of 0 can't be downloaded in, so it is treated as missing
+pub fn parse_transfer_size(extra: &[u8]) -> usize {
+    match extra {
+        [_, DFU_FUNCTIONAL_DESCRIPTOR, _, _, _, low, high, ..] => {
+            match u16::from_le_bytes([*low, *high]) as usize {
+                0 => DEFAULT_TRANSFER_SIZE,
+                transfer_size => transfer_size,
+            }
+        }
+        _ => DEFAULT_TRANSFER_SIZE,
+    }
+}
+
+fn find_dfu_device() -> Result<Option<rusb::Device<GlobalContext>>> {
+    for device in rusb::devices()?.iter() {
+        let descriptor = device.device_descriptor()?;
+        if descriptor.vendor_id() == VENDOR_ID && descriptor.product_id() == DFU_PRODUCT_ID {
+            return Ok(Some(device));
+        }
+    }
+    Ok(None)
+}
+
+// Opens the DFU interface of the bootloader, returning it with the largest block it accepts
+fn open_dfu_device(device: rusb::Device<GlobalContext>) -> Result<(RusbTransport, usize)> {
+    let config = device.active_config_descriptor()?;
+    let descriptor = config
+        .interfaces()
+        .flat_map(|interface| interface.descriptors())
+        .find(|descriptor| {
+            descriptor.class_code() == DFU_CLASS && descriptor.sub_class_code() == DFU_SUBCLASS
+        })
+        .ok_or_else(|| anyhow!("Bootloader has no DFU interface"))?;
+
+    let transfer_size = parse_transfer_size(descriptor.extra());
+
+    let interface = descriptor.interface_number();
+    let handle = device.open()?;
+    handle.claim_interface(interface)?;
+
+    Ok((RusbTransport { handle, interface }, transfer_size))
+}
+
+fn emit_progress(
+    handle: &tauri::AppHandle,
+    stage: FirmwareUpdateStage,
+    bytes_written: usize,
+    total_bytes: usize,
+) {
+    handle
+        .emit(
+            "firmware-update-progress",
+            FirmwareUpdateProgress {
+                stage,
+                bytes_written,
+                total_bytes,
+            },
+        )
+        .unwrap();
+}
+
+// Reboots the connected pad into its bootloader, unless it is already there, and flashes the image at `path`
+pub fn update_firmware(handle: &tauri::AppHandle, path: &str) -> Result<()> {
+    let image = std::fs::read(path)?;
+    let firmware = verify_image(&image, VENDOR_ID, PRODUCT_ID)?;
+    let total_bytes = firmware.len();
+
+    let device = match find_dfu_device()? {
+        Some(device) => device,
+        None => {
+            let connected = {
+                let state_device_info = handle.state::<Mutex<Option<DeviceInfo>>>();
+                let state_device_info = state_device_info.lock().unwrap();
+                state_device_info.is_some()
+            };
+            if !connected {
+                bail!("No device connected");
+            }
+
+            emit_progress(handle, FirmwareUpdateStage::Rebooting, 0, total_bytes);
+            {
+                let output_queue = handle.state::<Mutex<OutputQueue>>();
+                let mut output_queue = output_queue.lock().unwrap();
+                let mut report = vec![BOOTLOADER_REPORT_ID];
+                report.extend(BOOTLOADER_MAGIC);
+                output_queue.push(report);
+            }
+
+            let started_at = Instant::now();
+            loop {
+                if let Some(device) = find_dfu_device()? {
+                    break device;
+                }
+                if started_at.elapsed() > BOOTLOADER_TIMEOUT {
+                    bail!("Device didn't enter its bootloader");
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+    };
+
+    let (mut transport, transfer_size) = open_dfu_device(device)?;
+    println!(
+        "Downloading {} bytes in blocks of {}",
+        total_bytes, transfer_size
+    );
+
+    download(&mut transport, firmware, transfer_size, |bytes_written| {
+        emit_progress(
+            handle,
+            FirmwareUpdateStage::Downloading,
+            bytes_written,
+            total_bytes,
+        );
+    })?;
+
+    emit_progress(handle, FirmwareUpdateStage::Done, total_bytes, total_bytes);
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use crate::config::{Action, ApplicationProfile, DisplayConfig, KeyCombination, Modifier};
+
+// Output reports for pads with a text display, each padded to DISPLAY_REPORT_LENGTH
+// [DISPLAY_REPORT_ID, DISPLAY_CLEAR]
+// [DISPLAY_REPORT_ID, DISPLAY_TEXT, row, column, length, text...]
+// [DISPLAY_REPORT_ID, DISPLAY_SHOW]
+// Text is ASCII, the firmware draws it with its own font and shows it once DISPLAY_SHOW arrives
+pub const DISPLAY_REPORT_ID: u8 = 0x03;
+pub const DISPLAY_REPORT_LENGTH: usize = 32;
+pub const DISPLAY_CLEAR: u8 = 0x00;
+pub const DISPLAY_TEXT: u8 = 0x01;
+pub const DISPLAY_SHOW: u8 = 0x02;
+const DISPLAY_TEXT_HEADER: usize = 5;
+
+// Keys are drawn in the same grid as on the pad
+const KEY_COLUMNS: usize = 3;
+const KEY_COUNT: usize = 12;
+
+// Lays out the profile name, then the label of each key, then the encoder
+// Labels are the display names of the bindings for the held modifiers
+pub fn render_display(
+    config: &DisplayConfig,
+    profile: Option<(&str, &ApplicationProfile)>,
+    modifiers: &HashSet<Modifier>,
+) -> Vec<String> {
+    let (profile_name, profile) = match profile {
+        Some(profile) => profile,
+        None => return fit_lines(config, vec!["No profile".to_string()]),
+    };
+
+    let modifiers = if modifiers.is_empty() {
+        None
+    } else {
+        Some(modifiers.clone())
+    };
+    let label = |action: Action| {
+        profile
+            .get_binding(&KeyCombination {
+                modifiers: modifiers.clone(),
+                action,
+            })
+            .map(|command| command.display_name)
+            .unwrap_or_default()
+    };
+
+    let mut lines = vec![profile_name.to_string()];
+
+    let cell_width = config.columns / KEY_COLUMNS;
+    let ids = (0..KEY_COUNT as u8).collect::<Vec<u8>>();
+    for row in ids.chunks(KEY_COLUMNS) {
+        let line = row
+            .iter()
+            .map(|id| {
+                // Leave a space between cells
+                let label = truncate(&label(Action::ButtonPress { id: *id }), cell_width.saturating_sub(1));
+                format!("{:<width$}", label, width = cell_width)
+            })
+            .collect::<String>();
+        lines.push(line.trim_end().to_string());
+    }
+
+    let decrement = label(Action::EncoderDecrement { id: 0 });
+    let increment = label(Action::EncoderIncrement { id: 0 });
+    if !decrement.is_empty() || !increment.is_empty() {
+        lines.push(format!("<{} >{}", decrement, increment));
+    }
+
+    fit_lines(config, lines)
+}
+
+// Reports which clear the display, draw the lines and show them
+pub fn display_reports(lines: &[String]) -> Vec<Vec<u8>> {
+    let mut reports = vec![pad(vec![DISPLAY_REPORT_ID, DISPLAY_CLEAR])];
+
+    let chunk_length = DISPLAY_REPORT_LENGTH - DISPLAY_TEXT_HEADER;
+    for (row, line) in lines.iter().enumerate() {
+        for (i, chunk) in line.as_bytes().chunks(chunk_length).enumerate() {
+            let mut report = vec![
+                DISPLAY_REPORT_ID,
+                DISPLAY_TEXT,
+                row as u8,
+                (i * chunk_length) as u8,
+                chunk.len() as u8,
+            ];
+            report.extend(chunk);
+            reports.push(pad(report));
+        }
+    }
+
+    reports.push(pad(vec![DISPLAY_REPORT_ID, DISPLAY_SHOW]));
+    reports
+}
+
+fn fit_lines(config: &DisplayConfig, lines: Vec<String>) -> Vec<String> {
+    lines
+        .into_iter()
+        .take(config.rows)
+        .map(|line| truncate(&line, config.columns))
+        .collect()
+}
+
+// Replaces anything the display's font can't draw and cuts the text to `width` characters
+fn truncate(text: &str, width: usize) -> String {
+    text.chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' })
+        .take(width)
+        .collect()
+}
+
+fn pad(mut report: Vec<u8>) -> Vec<u8> {
+    report.resize(DISPLAY_REPORT_LENGTH, 0);
+    report
+}
@@ -9,3 +9,38 @@ pub struct ShowRadialMenu {
 }
 
 pub type SelectedRadialMenuItem = config::RadialMenuItem;
+
+// Payload of the toggle-changed event
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToggleChanged {
+    pub id: String,
+    pub on: bool,
+}
+
+// Results of operations such as Run, for the macro log
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroLog {
+    pub operation: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareUpdateProgress {
+    pub stage: FirmwareUpdateStage,
+    pub bytes_written: usize,
+    pub total_bytes: usize,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FirmwareUpdateStage {
+    Rebooting,
+    Downloading,
+    Done,
+}
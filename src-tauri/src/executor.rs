@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::{Command, ConcurrencyPolicy, KeyCombination, Operation};
+
+// Cancelled commands notice within this long while waiting in a delay
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+// Work for the executor thread
+#[derive(Clone, Debug)]
+pub enum Job {
+    // Runs the command `repetitions` times, `reverse` steps cycles backwards
+    // `binding` is the key combination it is bound to, None when run from the UI
+    Run {
+        command: Command,
+        binding: Option<KeyCombination>,
+        repetitions: u64,
+        reverse: bool,
+    },
+    // Lets go of whatever the command left held when its button was released
+    Release {
+        command: Command,
+        binding: KeyCombination,
+    },
+}
+
+// Shared with a running command so it can be stopped between operations
+#[derive(Clone, Debug, Default)]
+pub struct MacroHandle {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl MacroHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Sleeps for `duration`, returning early if cancelled
+    pub fn sleep(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while !self.is_cancelled() {
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+            std::thread::sleep((until - now).min(CANCEL_CHECK_INTERVAL));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Task {
+    pub job: Job,
+    pub handle: MacroHandle,
+}
+
+struct Scheduled {
+    handle: MacroHandle,
+    display_name: String,
+    binding: Option<KeyCombination>,
+    // Releases of the binding which arrived while this was scheduled, run after it
+    releases: Vec<Job>,
+}
+
+// Commands run in order on their own thread so long macros don't hold up reading the device
+// Keeps track of the commands running or waiting to run so they can be cancelled
+pub struct Executor {
+    sender: Sender<Task>,
+    scheduled: Vec<Scheduled>,
+    next_id: u64,
+}
+
+impl Executor {
+    // Queued tasks are received from the returned receiver
+    pub fn new() -> (Self, Receiver<Task>) {
+        let (sender, receiver) = mpsc::channel();
+        let executor = Executor {
+            sender,
+            scheduled: vec![],
+            next_id: 1,
+        };
+        (executor, receiver)
+    }
+
+    // Queues the job, or returns it if it should run on a thread of its own
+    // `macros` are looked through for AbortMacros
+    pub fn push(&mut self, job: Job, macros: &HashMap<String, Vec<Operation>>) -> Option<Task> {
+        let (command, binding) = match &job {
+            // Releases run after the command they release, on the same thread
+            // If it has already finished they run straight away, rather than waiting behind the queue
+            // Releases aren't cancellable so they still run after an abort
+            Job::Release { binding, .. } => {
+                match self
+                    .scheduled
+                    .iter_mut()
+                    .rev()
+                    .find(|scheduled| scheduled.binding.as_ref() == Some(binding))
+                {
+                    Some(scheduled) => {
+                        scheduled.releases.push(job);
+                        return None;
+                    }
+                    None => {
+                        return Some(Task {
+                            job,
+                            handle: MacroHandle::default(),
+                        })
+                    }
+                }
+            }
+            Job::Run {
+                command, binding, ..
+            } => (command, binding.clone()),
+        };
+
+        let policy = if command.contains_abort(macros) {
+            ConcurrencyPolicy::Parallel
+        } else {
+            command.concurrency
+        };
+
+        match policy {
+            ConcurrencyPolicy::Ignore if self.is_scheduled(&binding) => {
+                println!("Already running: {}", command.display_name);
+                return None;
+            }
+            ConcurrencyPolicy::Restart => {
+                for scheduled in self.scheduled.iter() {
+                    if binding.is_some() && scheduled.binding == binding {
+                        scheduled.handle.cancel();
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let handle = MacroHandle {
+            id: self.next_id,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        self.next_id += 1;
+        self.scheduled.push(Scheduled {
+            handle: handle.clone(),
+            display_name: command.display_name.clone(),
+            binding,
+            releases: vec![],
+        });
+
+        let task = Task { job, handle };
+        if policy == ConcurrencyPolicy::Parallel {
+            return Some(task);
+        }
+        self.send(task);
+        None
+    }
+
+    fn send(&self, task: Task) {
+        if let Err(e) = self.sender.send(task) {
+            eprintln!("Executor has stopped: {}", e);
+        }
+    }
+
+    // Commands are told apart by their binding, those run from the UI are never ignored or restarted
+    fn is_scheduled(&self, binding: &Option<KeyCombination>) -> bool {
+        binding.is_some()
+            && self
+                .scheduled
+                .iter()
+                .any(|scheduled| scheduled.binding == *binding && !scheduled.handle.is_cancelled())
+    }
+
+    // Called once a task has run or been skipped
+    // Returns the releases waiting on it, which the caller runs next
+    pub fn finish(&mut self, handle: &MacroHandle) -> Vec<Job> {
+        let mut releases = vec![];
+        self.scheduled.retain_mut(|scheduled| {
+            if scheduled.handle.id != handle.id {
+                return true;
+            }
+            releases.append(&mut scheduled.releases);
+            false
+        });
+        releases
+    }
+
+    // Cancels every running and queued command
+    pub fn abort_all(&mut self) {
+        for scheduled in self.scheduled.iter() {
+            println!("Aborting: {}", scheduled.display_name);
+            scheduled.handle.cancel();
+        }
+    }
+}
@@ -1,36 +1,71 @@
-use crate::macropad_state::{ButtonState, MacropadState};
+use std::time::Instant;
+
+use crate::macropad_state::{ButtonState, EncoderTurn, MacropadState};
 use crate::config::Action;
 
 pub const VENDOR_ID: u16 = 0x1209;
 pub const PRODUCT_ID: u16 = 0x001;
 pub const USAGE_PAGE: u16 = 0xFF;
 pub const USAGE: u16 = 0x01;
+// Buttons in the report, the first of the buttons in MacropadState
+pub const PAD_BUTTON_COUNT: usize = 12;
 
 // Reports are sent on all change events
 // Consequently there should only be one change event per report
 // If somehow multiple change events are sent, only the last one will be processed
 // All updates are persisted so that any inconsistencies can be corrected by the latest accurate report
+//
+// Returns the number of encoder detents covered by the action alongside it, 1 for button actions
 pub fn handle_report(
     macropad_state: MacropadState,
-    report: [u8; 2],
-) -> (MacropadState, Action) {
+    report: &[u8],
+) -> (MacropadState, Action, u8) {
+    handle_report_at(macropad_state, report, None, Instant::now())
+}
+
+// As handle_report, for a report received at `now`, e.g. when replaying a trace
+// `step_counts` is whether the firmware sends step counts, if it is known
+pub fn handle_report_at(
+    macropad_state: MacropadState,
+    report: &[u8],
+    step_counts: Option<bool>,
+    now: Instant,
+) -> (MacropadState, Action, u8) {
+    if report.len() < 2 {
+        eprintln!("Report too short: {:?}", report);
+        return (macropad_state, Action::None, 0);
+    }
+
     // First 12 bits of the report
     let buttons = ((report[1] as u16) << 8) | (report[0] as u16);
-    // Next 2 bits as a 2 bit signed integer
-    let mut encoders = vec![(report[1] >> 4) & 0b11].into_iter().map(|x| match x {
-        0b00 => 0,
-        0b01 => 1,
-        0b11 => -1,
-        _ => {
-            eprintln!("Invalid encoder value: {}", x);
-            0
-        }
-    });
+    // Encoder push switches follow the 2 encoder bits
+    let encoder_switches = buttons >> 14;
+    // Newer firmware appends a byte per encoder with the signed number of detents turned since the last report
+    // Otherwise the next 2 bits are a 2 bit signed integer which returns to 0 between detents
+    // Without firmware info, longer reports are taken to have step counts, though some pads pad their reports
+    let step_counts = step_counts.unwrap_or(report.len() > 2);
+    let encoders: Vec<i8> = if step_counts {
+        report[2..].iter().map(|x| *x as i8).collect()
+    } else {
+        vec![(report[1] >> 4) & 0b11]
+            .into_iter()
+            .map(|x| match x {
+                0b00 => 0,
+                0b01 => 1,
+                0b11 => -1,
+                _ => {
+                    eprintln!("Invalid encoder value: {}", x);
+                    0
+                }
+            })
+            .collect()
+    };
 
-    let mut new_macropad_state = macropad_state.clone();
+    let mut new_macropad_state = macropad_state;
     let mut action = Action::None;
+    let mut steps = 1;
 
-    for i in 0..12 {
+    for i in 0..PAD_BUTTON_COUNT {
         let button_pressed = (buttons & (1 << i)) != 0;
 
         match (macropad_state.buttons[i], button_pressed) {
@@ -38,7 +73,7 @@ pub fn handle_report(
                 // Button was pressed
                 println!("Button {} pressed", i);
                 new_macropad_state.buttons[i] = ButtonState::Held {
-                    pressed_at: std::time::Instant::now(),
+                    pressed_at: now,
                 };
                 action = Action::ButtonPress { id: i as u8 };
             }
@@ -52,21 +87,97 @@ pub fn handle_report(
         }
     }
 
-    for i in 0..encoders.clone().count() {
-        let encoder_state = encoders.nth(i).unwrap();
-        match (macropad_state.encoders[i], encoder_state) {
-            (0, 1) => {
-                println!("Encoder {} incremented", i);
-                action = Action::EncoderIncrement { id: i as u8 };
+    for i in 0..macropad_state.encoder_switches.len() {
+        let switch_pressed = (encoder_switches & (1 << i)) != 0;
+
+        match (macropad_state.encoder_switches[i], switch_pressed) {
+            (ButtonState::None, true) => {
+                println!("Encoder {} pushed", i);
+                new_macropad_state.encoder_switches[i] = ButtonState::Held {
+                    pressed_at: now,
+                };
+                action = Action::EncoderPush { id: i as u8 };
             }
-            (0, -1) => {
-                println!("Encoder {} decremented", i);
-                action = Action::EncoderDecrement { id: i as u8 };
+            (ButtonState::Held { pressed_at: _ }, false) => {
+                println!("Encoder {} released", i);
+                new_macropad_state.encoder_switches[i] = ButtonState::None;
+                action = Action::EncoderRelease { id: i as u8 };
             }
             _ => {}
         }
-        new_macropad_state.encoders[i] = encoder_state;
     }
 
-    return (new_macropad_state, action);
-}
\ No newline at end of file
+    for (i, encoder_state) in encoders
+        .into_iter()
+        .enumerate()
+        .take(macropad_state.encoders.len())
+    {
+        let delta = match (step_counts, macropad_state.encoders[i]) {
+            (true, _) => encoder_state,
+            (false, 0) => encoder_state,
+            _ => 0,
+        };
+        if delta != 0 {
+            (new_macropad_state, action, steps) = handle_turn(new_macropad_state, i as u8, delta, now);
+        }
+        // Step counts are relative so there is no position to remember
+        new_macropad_state.encoders[i] = if step_counts { 0 } else { encoder_state };
+    }
+
+    (new_macropad_state, action, steps)
+}
+
+// For encoders turned by `delta` detents outside of a report, such as MIDI controllers
+// Returns Action::None with no movement or for an encoder the pad doesn't have
+pub fn handle_turn(
+    macropad_state: MacropadState,
+    id: u8,
+    delta: i8,
+    now: Instant,
+) -> (MacropadState, Action, u8) {
+    if delta == 0 || id as usize >= macropad_state.encoder_turns.len() {
+        return (macropad_state, Action::None, 1);
+    }
+
+    let action = if delta > 0 {
+        println!("Encoder {} incremented by {}", id, delta);
+        Action::EncoderIncrement { id }
+    } else {
+        println!("Encoder {} decremented by {}", id, delta.unsigned_abs());
+        Action::EncoderDecrement { id }
+    };
+    let mut new_macropad_state = macropad_state;
+    new_macropad_state.encoder_turns[id as usize] = Some(EncoderTurn {
+        at: now,
+        direction: delta.signum(),
+    });
+
+    (new_macropad_state, action, delta.unsigned_abs())
+}
+
+// For sources which only have buttons, such as keyboards, rather than reports
+// Returns Action::None if the button is already in that state
+pub fn handle_button(
+    macropad_state: MacropadState,
+    id: u8,
+    pressed: bool,
+    now: Instant,
+) -> (MacropadState, Action) {
+    let mut new_macropad_state = macropad_state;
+
+    let action = match (macropad_state.buttons.get(id as usize), pressed) {
+        (Some(ButtonState::None), true) => {
+            println!("Button {} pressed", id);
+            new_macropad_state.buttons[id as usize] = ButtonState::Held { pressed_at: now };
+            Action::ButtonPress { id }
+        }
+        (Some(ButtonState::Held { pressed_at: _ }), false) => {
+            println!("Button {} released", id);
+            new_macropad_state.buttons[id as usize] = ButtonState::None;
+            Action::ButtonRelease { id }
+        }
+        _ => Action::None,
+    };
+
+    (new_macropad_state, action)
+}
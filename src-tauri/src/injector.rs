@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use enigo::{Button, Coordinate, Direction, Enigo, InputResult, Key, Keyboard, Mouse};
+
+// Some apps miss a drag if the button is released as soon as the pointer moves
+const DRAG_STEP_DELAY: Duration = Duration::from_millis(20);
+
+// Wraps the shared enigo instance and keeps track of the keys it is holding down
+// If the release never arrives, e.g. the device was unplugged mid-press, the held keys can be released
+// The sink is generic so tests can record what would be sent
+pub struct Injector<S = Enigo> {
+    pub sink: S,
+    pressed_keys: HashSet<Key>,
+}
+
+impl<S: Keyboard + Mouse> Injector<S> {
+    pub fn new(sink: S) -> Self {
+        Injector {
+            sink,
+            pressed_keys: HashSet::new(),
+        }
+    }
+
+    // Releases are always sent, so a KeyRelease can let go of a key held elsewhere, e.g. on a real keyboard
+    pub fn key(&mut self, key: Key, direction: Direction) -> InputResult<()> {
+        self.sink.key(key, direction)?;
+        match direction {
+            Direction::Press => {
+                self.pressed_keys.insert(key);
+            }
+            Direction::Release => {
+                self.pressed_keys.remove(&key);
+            }
+            Direction::Click => {}
+        }
+
+        Ok(())
+    }
+
+    // Only releases the key if it was pressed here and is still held
+    // Used when a command's button is released, as it may not have run every KeyPress it has
+    pub fn release_held(&mut self, key: Key) -> InputResult<()> {
+        if self.is_pressed(key) {
+            self.key(key, Direction::Release)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    // Holds the other keys while tapping the last
+    // Keys already held, e.g. by an earlier KeyPress, are left held afterwards
+    pub fn shortcut(&mut self, keys: &[Key]) -> InputResult<()> {
+        let (key, modifiers) = match keys.split_last() {
+            Some(keys) => keys,
+            None => return Ok(()),
+        };
+
+        let mut pressed = vec![];
+        for modifier in modifiers {
+            if self.is_pressed(*modifier) || pressed.contains(modifier) {
+                continue;
+            }
+            self.key(*modifier, Direction::Press)?;
+            pressed.push(*modifier);
+        }
+
+        let result = self.key(*key, Direction::Click);
+        // Released even if the tap failed so the modifiers aren't left stuck
+        for modifier in pressed.iter().rev() {
+            self.key(*modifier, Direction::Release)?;
+        }
+        result
+    }
+
+    // Newlines are typed as Enter as not every app accepts them as text
+    pub fn text(&mut self, text: &str) -> InputResult<()> {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.key(Key::Return, Direction::Click)?;
+            }
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if !line.is_empty() {
+                self.sink.text(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn drag(
+        &mut self,
+        button: Button,
+        x: i32,
+        y: i32,
+        coordinate: Coordinate,
+    ) -> InputResult<()> {
+        self.sink.button(button, Direction::Press)?;
+        std::thread::sleep(DRAG_STEP_DELAY);
+        let result = self.sink.move_mouse(x, y, coordinate);
+        std::thread::sleep(DRAG_STEP_DELAY);
+        // Released even if the move failed so the button isn't left stuck
+        self.sink.button(button, Direction::Release)?;
+        result
+    }
+
+    // Release every key that was pressed but not yet released
+    pub fn release_all(&mut self) {
+        for key in self.pressed_keys.drain() {
+            println!("Releasing held key: {:?}", key);
+            if let Err(e) = self.sink.key(key, Direction::Release) {
+                eprintln!("Failed to release key {:?}: {}", key, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::config::KeyboardConfig;
+
+// Key codes of a keyboard to button ids
+#[derive(Clone, Debug, Default)]
+pub struct KeyMap {
+    buttons: HashMap<u16, u8>,
+}
+
+impl KeyMap {
+    // `parse_key` turns a key name into the platform's key code
+    pub fn new(config: &KeyboardConfig, parse_key: impl Fn(&str) -> Option<u16>) -> Result<Self> {
+        let buttons = config
+            .keys
+            .iter()
+            .map(|(key, id)| {
+                parse_key(key)
+                    .map(|code| (code, *id))
+                    .ok_or_else(|| anyhow!("Unknown key: {}", key))
+            })
+            .collect::<Result<HashMap<u16, u8>>>()?;
+
+        Ok(KeyMap { buttons })
+    }
+
+    pub fn get_button(&self, code: u16) -> Option<u8> {
+        self.buttons.get(&code).copied()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn parse_key(key: &str) -> Option<u16> {
+    key.parse::<evdev::Key>().ok().map(|key| key.code())
+}
+
+// Each keyboard is listened to on its own thread, and looked for again if it goes away
+#[cfg(target_os = "linux")]
+pub fn listen_keyboards(handle: &tauri::AppHandle, configs: &[KeyboardConfig]) {
+    let threads = configs
+        .iter()
+        .cloned()
+        .map(|config| {
+            let handle = handle.clone();
+            std::thread::spawn(move || loop {
+                if let Err(e) = listen_keyboard(&handle, &config) {
+                    eprintln!("Failed to read from keyboard {}: {}", config.device, e);
+                }
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn listen_keyboard(handle: &tauri::AppHandle, config: &KeyboardConfig) -> Result<()> {
+    use evdev::InputEventKind;
+    use regex::Regex;
+
+    let key_map = KeyMap::new(config, parse_key)?;
+    let re = Regex::new(&config.device)?;
+
+    let (path, mut device) = match evdev::enumerate()
+        .find(|(_, device)| device.name().map(|name| re.is_match(name)).unwrap_or(false))
+    {
+        Some(device) => device,
+        // Not plugged in
+        None => return Ok(()),
+    };
+
+    // Stops the keys from also being typed
+    device.grab()?;
+    println!("Grabbed keyboard: {:?} ({})", device.name(), path.display());
+
+    loop {
+        for event in device.fetch_events()? {
+            if let InputEventKind::Key(key) = event.kind() {
+                let id = match key_map.get_button(key.code()) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                // 0 is up, 1 is down and 2 is autorepeat
+                match event.value() {
+                    0 => crate::process_button(handle, id, false),
+                    1 => crate::process_button(handle, id, true),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listen_keyboards(_handle: &tauri::AppHandle, _configs: &[KeyboardConfig]) {
+    eprintln!("Keyboards can only be used as pads on Linux");
+}
@@ -0,0 +1,222 @@
+use enigo::Key;
+
+// Key names used by key operations, matched case insensitively
+// Any other single character is typed as that character
+//
+// Modifiers      SHIFT, CTRL/CONTROL, ALT, META/WIN/SUPER/CMD
+//                LSHIFT, RSHIFT, LCTRL, RCTRL, LALT, RALT/ALTGR, LMETA/LWIN, RMETA/RWIN
+// Editing        ENTER/RETURN, TAB, SPACE, BACKSPACE, DEL/DELETE, INS/INSERT, ESC/ESCAPE
+// Navigation     UP, DOWN, LEFT, RIGHT (or UPARROW etc.), HOME, END, PAGEUP/PGUP, PAGEDOWN/PGDN
+// Locks          CAPSLOCK, NUMLOCK, SCROLLLOCK
+// System         PRINTSCREEN/PRTSC, PAUSE, MENU/APPS, HELP
+// Function       F1 to F24
+// Numpad         NUM0 to NUM9, NUMADD, NUMSUBTRACT, NUMMULTIPLY, NUMDIVIDE, NUMDECIMAL, NUMENTER
+// Media          PLAYPAUSE, STOP, NEXTTRACK, PREVTRACK, VOLUMEUP, VOLUMEDOWN, MUTE, MICMUTE
+// Browser        BROWSERBACK, BROWSERFORWARD, BROWSERREFRESH, BROWSERHOME, BROWSERSEARCH
+// Display        BRIGHTNESSUP, BRIGHTNESSDOWN
+//
+// Some keys only exist on some platforms and are unknown elsewhere:
+// MICMUTE and the brightness keys aren't available on Windows
+// Windows only has a single Enter key, so NUMENTER is the same as ENTER there
+pub fn parse_key(name: &str) -> Option<Key> {
+    let key = match name.to_uppercase().as_str() {
+        // Modifiers
+        "SHIFT" => Key::Shift,
+        "CTRL" | "CONTROL" => Key::Control,
+        "ALT" => Key::Alt,
+        "META" | "WIN" | "SUPER" | "CMD" => Key::Meta,
+        "LSHIFT" => Key::LShift,
+        "RSHIFT" => Key::RShift,
+        "LCTRL" | "LCONTROL" => Key::LControl,
+        "RCTRL" | "RCONTROL" => Key::RControl,
+        #[cfg(target_os = "windows")]
+        "LALT" => Key::LMenu,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "LALT" => Key::Alt,
+        #[cfg(target_os = "windows")]
+        "RALT" | "ALTGR" => Key::RMenu,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "RALT" | "ALTGR" => Key::Other(0xffea),
+        #[cfg(target_os = "windows")]
+        "LMETA" | "LWIN" => Key::LWin,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "LMETA" | "LWIN" => Key::Meta,
+        #[cfg(target_os = "windows")]
+        "RMETA" | "RWIN" => Key::RWin,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "RMETA" | "RWIN" => Key::Other(0xffec),
+
+        // Editing
+        "ENTER" | "RETURN" => Key::Return,
+        "TAB" => Key::Tab,
+        "SPACE" => Key::Space,
+        "BACKSPACE" => Key::Backspace,
+        "DEL" | "DELETE" => Key::Delete,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "INS" | "INSERT" => Key::Insert,
+        "ESC" | "ESCAPE" => Key::Escape,
+
+        // Navigation
+        "UP" | "UPARROW" => Key::UpArrow,
+        "DOWN" | "DOWNARROW" => Key::DownArrow,
+        "LEFT" | "LEFTARROW" => Key::LeftArrow,
+        "RIGHT" | "RIGHTARROW" => Key::RightArrow,
+        "HOME" => Key::Home,
+        "END" => Key::End,
+        "PAGEUP" | "PGUP" => Key::PageUp,
+        "PAGEDOWN" | "PGDN" => Key::PageDown,
+
+        // Locks
+        "CAPSLOCK" => Key::CapsLock,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "NUMLOCK" => Key::Numlock,
+        #[cfg(target_os = "windows")]
+        "SCROLLLOCK" => Key::Scroll,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "SCROLLLOCK" => Key::ScrollLock,
+
+        // System
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "PRINTSCREEN" | "PRTSC" => Key::PrintScr,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "PAUSE" => Key::Pause,
+        #[cfg(target_os = "windows")]
+        "MENU" | "APPS" => Key::Apps,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "MENU" | "APPS" => Key::LMenu,
+        "HELP" => Key::Help,
+
+        // Function
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        "F13" => Key::F13,
+        "F14" => Key::F14,
+        "F15" => Key::F15,
+        "F16" => Key::F16,
+        "F17" => Key::F17,
+        "F18" => Key::F18,
+        "F19" => Key::F19,
+        "F20" => Key::F20,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "F21" => Key::F21,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "F22" => Key::F22,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "F23" => Key::F23,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "F24" => Key::F24,
+
+        // Numpad, X11 keysyms on Linux
+        #[cfg(target_os = "windows")]
+        "NUM0" => Key::Numpad0,
+        #[cfg(target_os = "windows")]
+        "NUM1" => Key::Numpad1,
+        #[cfg(target_os = "windows")]
+        "NUM2" => Key::Numpad2,
+        #[cfg(target_os = "windows")]
+        "NUM3" => Key::Numpad3,
+        #[cfg(target_os = "windows")]
+        "NUM4" => Key::Numpad4,
+        #[cfg(target_os = "windows")]
+        "NUM5" => Key::Numpad5,
+        #[cfg(target_os = "windows")]
+        "NUM6" => Key::Numpad6,
+        #[cfg(target_os = "windows")]
+        "NUM7" => Key::Numpad7,
+        #[cfg(target_os = "windows")]
+        "NUM8" => Key::Numpad8,
+        #[cfg(target_os = "windows")]
+        "NUM9" => Key::Numpad9,
+        #[cfg(target_os = "windows")]
+        "NUMADD" => Key::Add,
+        #[cfg(target_os = "windows")]
+        "NUMSUBTRACT" => Key::Subtract,
+        #[cfg(target_os = "windows")]
+        "NUMMULTIPLY" => Key::Multiply,
+        #[cfg(target_os = "windows")]
+        "NUMDIVIDE" => Key::Divide,
+        #[cfg(target_os = "windows")]
+        "NUMDECIMAL" => Key::Decimal,
+        #[cfg(target_os = "windows")]
+        "NUMENTER" => Key::Return,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        name @ ("NUM0" | "NUM1" | "NUM2" | "NUM3" | "NUM4" | "NUM5" | "NUM6" | "NUM7" | "NUM8"
+        | "NUM9") => Key::Other(0xffb0 + name[3..].parse::<u32>().unwrap()),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "NUMADD" => Key::Other(0xffab),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "NUMSUBTRACT" => Key::Other(0xffad),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "NUMMULTIPLY" => Key::Other(0xffaa),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "NUMDIVIDE" => Key::Other(0xffaf),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "NUMDECIMAL" => Key::Other(0xffae),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "NUMENTER" => Key::Other(0xff8d),
+
+        // Media
+        "PLAYPAUSE" | "MEDIAPLAYPAUSE" => Key::MediaPlayPause,
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+        "STOP" | "MEDIASTOP" => Key::MediaStop,
+        "NEXTTRACK" | "MEDIANEXTTRACK" => Key::MediaNextTrack,
+        "PREVTRACK" | "MEDIAPREVTRACK" => Key::MediaPrevTrack,
+        "VOLUMEUP" => Key::VolumeUp,
+        "VOLUMEDOWN" => Key::VolumeDown,
+        "MUTE" | "VOLUMEMUTE" => Key::VolumeMute,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "MICMUTE" => Key::MicMute,
+
+        // Browser, XF86 keysyms on Linux
+        #[cfg(target_os = "windows")]
+        "BROWSERBACK" => Key::BrowserBack,
+        #[cfg(target_os = "windows")]
+        "BROWSERFORWARD" => Key::BrowserForward,
+        #[cfg(target_os = "windows")]
+        "BROWSERREFRESH" => Key::BrowserRefresh,
+        #[cfg(target_os = "windows")]
+        "BROWSERHOME" => Key::BrowserHome,
+        #[cfg(target_os = "windows")]
+        "BROWSERSEARCH" => Key::BrowserSearch,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BROWSERBACK" => Key::Other(0x1008ff26),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BROWSERFORWARD" => Key::Other(0x1008ff27),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BROWSERREFRESH" => Key::Other(0x1008ff29),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BROWSERHOME" => Key::Other(0x1008ff18),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BROWSERSEARCH" => Key::Other(0x1008ff1b),
+
+        // Display
+        #[cfg(target_os = "macos")]
+        "BRIGHTNESSUP" => Key::BrightnessUp,
+        #[cfg(target_os = "macos")]
+        "BRIGHTNESSDOWN" => Key::BrightnessDown,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BRIGHTNESSUP" => Key::Other(0x1008ff02),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "BRIGHTNESSDOWN" => Key::Other(0x1008ff03),
+
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Unicode(c.to_lowercase().next().unwrap_or(c)),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(key)
+}
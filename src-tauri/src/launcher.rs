@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+
+use crate::executor::MacroHandle;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Only web and mail links are opened, other schemes can start arbitrary handlers
+const URL_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunOutput {
+    // None if the process was killed by a signal
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Allow-list entries are regexes which must match the whole program or path
+pub fn is_allowed(allow_list: &[String], target: &str) -> bool {
+    allow_list.iter().any(|pattern| {
+        Regex::new(&format!("^(?:{})$", pattern))
+            .map(|re| re.is_match(target))
+            .unwrap_or(false)
+    })
+}
+
+pub fn check_allowed(allow_list: &[String], target: &str) -> Result<()> {
+    if !is_allowed(allow_list, target) {
+        bail!("{:?} isn't in the allow list", target);
+    }
+    Ok(())
+}
+
+pub fn check_url(url: &str) -> Result<()> {
+    if !URL_SCHEMES
+        .iter()
+        .any(|scheme| url.to_lowercase().starts_with(scheme))
+    {
+        bail!(
+            "Unsupported URL {:?}, must start with one of {:?}",
+            url,
+            URL_SCHEMES
+        );
+    }
+    Ok(())
+}
+
+pub fn build_command(
+    program: &str,
+    args: &[String],
+    cwd: &Option<String>,
+    env: &HashMap<String, String>,
+) -> std::process::Command {
+    let mut command = std::process::Command::new(program);
+    command.args(args).envs(env);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    command
+}
+
+// Starts the program without waiting, logging how it exits from another thread
+pub fn spawn(
+    mut command: std::process::Command,
+    log: impl FnOnce(Result<RunOutput>) + Send + 'static,
+) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    std::thread::spawn(move || {
+        log(child
+            .wait()
+            .map_err(anyhow::Error::from)
+            .map(|status| RunOutput {
+                exit_code: status.code(),
+                stdout: String::new(),
+                stderr: String::new(),
+            }));
+    });
+    Ok(())
+}
+
+// Runs the program to completion, killing it if it takes longer than `timeout` or the macro is cancelled
+pub fn run_and_wait(
+    mut command: std::process::Command,
+    timeout: Duration,
+    macro_handle: &MacroHandle,
+) -> Result<RunOutput> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Pipes are drained as the program runs so it can't block on a full pipe
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let started_at = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if macro_handle.is_cancelled() {
+            kill(&mut child);
+            bail!("Cancelled");
+        }
+        if started_at.elapsed() > timeout {
+            kill(&mut child);
+            bail!("Timed out after {} ms", timeout.as_millis());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    Ok(RunOutput {
+        exit_code: status.code(),
+        stdout: stdout
+            .join()
+            .map_err(|_| anyhow!("Failed to read stdout"))?,
+        stderr: stderr
+            .join()
+            .map_err(|_| anyhow!("Failed to read stderr"))?,
+    })
+}
+
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut pipe) = pipe {
+            if let Err(e) = pipe.read_to_end(&mut output) {
+                eprintln!("Failed to read output: {}", e);
+            }
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+fn kill(child: &mut Child) {
+    if let Err(e) = child.kill() {
+        eprintln!("Failed to kill process: {}", e);
+    }
+    let _ = child.wait();
+}
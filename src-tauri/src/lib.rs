@@ -1,11 +1,12 @@
-use std::collections::HashSet;
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::Receiver;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 use anyhow::Result;
-use enigo::{Direction, Enigo, Key, Keyboard, Mouse, Settings};
-use regex::Regex;
+use enigo::{Coordinate, Direction, Enigo, InputResult, Mouse, Settings};
 use serde::Serialize;
-use tauri::{Emitter, Manager, State};
+use tauri::{Emitter, Manager, RunEvent, State};
 use windows::{
     Win32::Foundation::HWND,
     Win32::System::ProcessStatus::K32GetModuleBaseNameW,
@@ -15,16 +16,57 @@ use windows::{
     },
 };
 
+pub mod clipboard;
+pub mod condition;
 pub mod config;
+pub mod debounce;
+pub mod device_info;
+pub mod dfu;
+pub mod display;
 pub mod events;
+pub mod executor;
 pub mod hid;
+pub mod injector;
+pub mod keyboard;
+pub mod keys;
+pub mod launcher;
 pub mod macropad_state;
+pub mod midi;
+pub mod output;
+pub mod resolver;
+pub mod serial;
+pub mod template;
+pub mod toggle;
+pub mod trace;
+pub mod transport;
+pub mod virtual_device;
+use crate::clipboard::{apply_transform, ClipboardState};
+use crate::condition::{ConditionContext, Variables};
 use crate::config::{
-    get_config_path, load_config, Action, AppConfig, ApplicationProfile, Command, KeyCombination,
-    Operation, RadialMenuItem,
+    get_config_path, held_keys, load_validated_config, parse_shortcut, Action, AppConfig,
+    ApplicationProfile, Command, LaunchConfig, Operation, RadialMenuItem, MAX_MACRO_DEPTH,
 };
-use crate::hid::{handle_report, PRODUCT_ID, USAGE, USAGE_PAGE, VENDOR_ID};
-use crate::macropad_state::{ButtonState, MacropadState};
+use crate::debounce::ReportFilter;
+use crate::device_info::DeviceInfo;
+use crate::display::{display_reports, render_display};
+use crate::executor::{Executor, Job, MacroHandle, Task};
+use crate::hid::{
+    handle_button, handle_report_at, handle_turn, PRODUCT_ID, USAGE, USAGE_PAGE, VENDOR_ID,
+};
+use crate::injector::Injector;
+use crate::keyboard::listen_keyboards;
+use crate::keys::parse_key;
+use crate::launcher::{build_command, check_allowed, check_url, run_and_wait, spawn, RunOutput};
+use crate::macropad_state::{ButtonState, CycleStates, MacropadState};
+use crate::midi::listen_midi;
+use crate::output::{led_report, OutputQueue};
+use crate::resolver::{resolve_action, PressedCombinations};
+use crate::serial::listen_serial;
+use crate::template::{render_template, TemplateContext};
+use crate::toggle::{load_toggles, save_toggles, ToggleStates};
+use crate::trace::{TraceWriter, TRACE_ENV};
+use crate::transport::{HidTransport, Transport};
+use crate::virtual_device::{listen_virtual, VIRTUAL_DEVICE_ENV};
 
 #[derive(Clone, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -33,58 +75,113 @@ struct CurrentWindow {
     app_name: String,
 }
 
+// The config as written is returned with its errors, so saving it from the UI never drops the invalid parts
+// Configs that can't be loaded are an error rather than the default, which the UI would save over them
 #[tauri::command]
-fn get_config(state: State<'_, Mutex<AppConfig>>) -> String {
-    let mut state = state.lock().unwrap();
-
-    let config = match load_config() {
-        Ok(config) => config,
-        Err(e) => {
-            eprintln!("Failed to load config: {}", e);
-            AppConfig::default()
-        }
-    };
+fn get_config(state: State<'_, Mutex<AppConfig>>) -> Result<String, String> {
+    let loaded_config = load_validated_config().map_err(|e| {
+        eprintln!("Failed to load config: {}", e);
+        e.to_string()
+    })?;
 
-    *state = config;
+    let mut state = state.lock().unwrap();
+    *state = loaded_config.usable();
 
-    serde_json::to_string(&*state).unwrap()
+    Ok(serde_json::to_string(&loaded_config).unwrap())
 }
 
 #[tauri::command]
-fn save_config(state: State<'_, Mutex<AppConfig>>, config_json: String) {
+fn save_config(
+    handle: tauri::AppHandle,
+    state: State<'_, Mutex<AppConfig>>,
+    config_json: String,
+) -> Result<(), String> {
     println!("Saving config: {}", config_json);
-    let mut state = state.lock().unwrap();
-    *state = serde_json::from_str(&config_json).unwrap();
+    // Invalid configs are rejected rather than saved
+    let config: AppConfig = serde_json::from_str(&config_json).map_err(|e| e.to_string())?;
+    config.validate().map_err(|e| e.to_string())?;
+    {
+        let mut state = state.lock().unwrap();
+        *state = config;
+    }
 
     let config_path = get_config_path();
     std::fs::write(config_path, config_json).unwrap();
+
+    refresh_outputs(&handle);
+    Ok(())
+}
+
+#[tauri::command]
+fn command_handler(handle: tauri::AppHandle, command: Command) {
+    queue_job(
+        &handle,
+        Job::Run {
+            command,
+            binding: None,
+            repetitions: 1,
+            reverse: false,
+        },
+    );
+}
+
+// Toggle ids to whether they are on
+#[tauri::command]
+fn get_toggles(state: State<'_, Mutex<ToggleStates>>) -> HashMap<String, bool> {
+    let state = state.lock().unwrap();
+    state.toggles.clone()
+}
+
+#[tauri::command]
+fn abort_macros(handle: tauri::AppHandle) {
+    abort_all_macros(&handle);
 }
 
+// The connected pad, if any
 #[tauri::command]
-fn command_handler(handle: tauri::AppHandle, state: State<'_, Mutex<Enigo>>, command: Command) {
-    let mut enigo = state.lock().unwrap();
-    handle_command(&handle, &mut *enigo, &command);
+fn get_device_info(state: State<'_, Mutex<Option<DeviceInfo>>>) -> Option<DeviceInfo> {
+    state.lock().unwrap().clone()
+}
+
+// Progress is reported through firmware-update-progress events
+#[tauri::command]
+fn update_firmware(handle: tauri::AppHandle, path: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = dfu::update_firmware(&handle, &path) {
+            eprintln!("Failed to update firmware: {}", e);
+            handle
+                .emit("firmware-update-failed", e.to_string())
+                .unwrap();
+        }
+    });
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Enigo actions need to share the same enigo instance
     let enigo = Enigo::new(&Settings::default()).unwrap();
+    let (executor, tasks) = Executor::new();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .manage(Mutex::new(enigo))
+        .manage(Mutex::new(Injector::new(enigo)))
         .manage(Mutex::new(CurrentWindow::default()))
         .manage(Mutex::new(AppConfig::default()))
-        .manage(Mutex::new(MacropadState {
-            buttons: [ButtonState::None; 12],
-            encoders: [0; 1],
-        }))
-        .setup(|app| {
+        .manage(Mutex::new(MacropadState::default()))
+        .manage(Mutex::new(PressedCombinations::default()))
+        .manage(Mutex::new(OutputQueue::default()))
+        .manage(Mutex::new(None::<DeviceInfo>))
+        .manage(Mutex::new(executor))
+        .manage(Mutex::new(ClipboardState::default()))
+        .manage(Mutex::new(ToggleStates::default()))
+        .manage(Mutex::new(CycleStates::default()))
+        .manage(Mutex::new(Variables::default()))
+        .setup(move |app| {
             let handle = app.handle().clone();
 
-            let config = match load_config() {
-                Ok(config) => config,
+            // The file is left as it is if it can't be loaded
+            let config = match load_validated_config() {
+                Ok(loaded_config) => loaded_config.usable(),
                 Err(e) => {
                     eprintln!("Failed to load config: {}", e);
                     AppConfig::default()
@@ -95,25 +192,229 @@ pub fn run() {
             let mut state_app_config = state_app_config.lock().unwrap();
             *state_app_config = config;
 
+            match load_toggles() {
+                Ok(toggles) => {
+                    let state_toggles = handle.state::<Mutex<ToggleStates>>();
+                    let mut state_toggles = state_toggles.lock().unwrap();
+                    *state_toggles = toggles;
+                }
+                Err(e) => eprintln!("Failed to load toggles: {}", e),
+            }
+
+            let executor_handle = handle.clone();
+            std::thread::spawn(move || {
+                run_executor(&executor_handle, tasks);
+            });
+
             let window_tracker_handle = handle.clone();
             std::thread::spawn(move || {
                 track_active_window(&window_tracker_handle);
             });
 
+            // Development and CI machines without a pad can feed reports from a virtual device instead
             let serial_handle = handle.clone();
+            let serial_config = state_app_config.serial.clone();
+            let midi_devices = state_app_config.midi_devices.clone();
+            let keyboards = state_app_config.keyboards.clone();
             std::thread::spawn(move || {
-                listen_hid(&serial_handle);
+                if let Ok(device) = std::env::var(VIRTUAL_DEVICE_ENV) {
+                    listen_virtual(&serial_handle, &device);
+                } else if let Some(serial_config) = serial_config {
+                    listen_serial(&serial_handle, &serial_config);
+                } else {
+                    listen_hid(&serial_handle);
+                }
             });
 
+            // MIDI control surfaces and keyboards are extra pads alongside whichever pad is used
+            if !midi_devices.is_empty() {
+                let midi_handle = handle.clone();
+                std::thread::spawn(move || {
+                    listen_midi(&midi_handle, &midi_devices);
+                });
+            }
+
+            if !keyboards.is_empty() {
+                let keyboard_handle = handle.clone();
+                std::thread::spawn(move || {
+                    listen_keyboards(&keyboard_handle, &keyboards);
+                });
+            }
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_config,
             save_config,
-            command_handler
+            command_handler,
+            abort_macros,
+            get_toggles,
+            get_device_info,
+            update_firmware
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|handle, event| {
+            if let RunEvent::Exit = event {
+                // Don't leave keys held down in the OS after the app has gone
+                reset_macropad(handle);
+            }
+        });
+}
+
+// Releases any keys held by commands and forgets the held buttons
+// Used whenever the release reports for held buttons can no longer be trusted to arrive
+fn reset_macropad(handle: &tauri::AppHandle) {
+    {
+        // Same lock order as listen_hid
+        let macropad_state = handle.state::<Mutex<MacropadState>>();
+        let mut macropad_state = macropad_state.lock().unwrap();
+        *macropad_state = MacropadState::default();
+
+        {
+            let pressed_combinations = handle.state::<Mutex<PressedCombinations>>();
+            let mut pressed_combinations = pressed_combinations.lock().unwrap();
+            pressed_combinations.clear();
+        }
+
+        lock_injector(handle).release_all();
+    }
+
+    turn_off_held_toggles(handle);
+}
+
+// Toggles holding keys are turned off once their keys have been released
+// Otherwise the next press would run `off` for keys which aren't held, and take another press to hold them again
+fn turn_off_held_toggles(handle: &tauri::AppHandle) {
+    let ids = {
+        let state_app_config = handle.state::<Mutex<AppConfig>>();
+        let state_app_config = state_app_config.lock().unwrap();
+        state_app_config.get_toggles_holding_keys()
+    };
+
+    let turned_off = {
+        let state_toggles = handle.state::<Mutex<ToggleStates>>();
+        let mut state_toggles = state_toggles.lock().unwrap();
+        let turned_off = state_toggles.turn_off(&ids);
+        if !turned_off.is_empty() {
+            if let Err(e) = save_toggles(&state_toggles) {
+                eprintln!("Failed to save toggles: {}", e);
+            }
+        }
+        turned_off
+    };
+    if turned_off.is_empty() {
+        return;
+    }
+
+    for id in turned_off {
+        println!("Toggle {}: off", id);
+        handle
+            .emit("toggle-changed", events::ToggleChanged { id, on: false })
+            .unwrap();
+    }
+    refresh_outputs(handle);
+}
+
+// Each state is locked in turn, so this never waits on more than one at a time
+fn get_condition_context(handle: &tauri::AppHandle) -> ConditionContext {
+    let (window_title, app_name) = {
+        let state_current_window = handle.state::<Mutex<CurrentWindow>>();
+        let state_current_window = state_current_window.lock().unwrap();
+        (
+            state_current_window.title.clone(),
+            state_current_window.app_name.clone(),
+        )
+    };
+    let held = {
+        let macropad_state = handle.state::<Mutex<MacropadState>>();
+        let macropad_state = macropad_state.lock().unwrap();
+        macropad_state.get_modifiers(&Action::None, std::time::Instant::now())
+    };
+    let toggles = {
+        let state_toggles = handle.state::<Mutex<ToggleStates>>();
+        let state_toggles = state_toggles.lock().unwrap();
+        state_toggles.clone()
+    };
+    let variables = {
+        let variables = handle.state::<Mutex<Variables>>();
+        let variables = variables.lock().unwrap();
+        variables.values.clone()
+    };
+
+    ConditionContext {
+        window_title,
+        app_name,
+        held,
+        toggles,
+        variables,
+        now: chrono::Local::now().time(),
+    }
+}
+
+fn get_window_title(handle: &tauri::AppHandle) -> String {
+    let state_current_window = handle.state::<Mutex<CurrentWindow>>();
+    let state_current_window = state_current_window.lock().unwrap();
+    state_current_window.title.clone()
+}
+
+// Queues the LEDs and display text for the current profile and held modifiers to be sent to the device
+fn refresh_outputs(handle: &tauri::AppHandle) {
+    let modifiers = {
+        let macropad_state = handle.state::<Mutex<MacropadState>>();
+        let macropad_state = macropad_state.lock().unwrap();
+        macropad_state.get_modifiers(&Action::None, std::time::Instant::now())
+    };
+
+    let display_config = {
+        let state_app_config = handle.state::<Mutex<AppConfig>>();
+        let state_app_config = state_app_config.lock().unwrap();
+        state_app_config.display.clone()
+    };
+
+    let toggles = {
+        let state_toggles = handle.state::<Mutex<ToggleStates>>();
+        let state_toggles = state_toggles.lock().unwrap();
+        state_toggles.toggles.clone()
+    };
+
+    let application_profile = get_application_profile(handle, &get_window_title(handle));
+    // Toggles show over the layers
+    let leds = application_profile
+        .as_ref()
+        .map(|(_, profile)| {
+            let mut leds = profile.get_leds(&modifiers);
+            leds.extend(profile.get_toggle_leds(&toggles));
+            leds
+        })
+        .unwrap_or_default();
+
+    let output_queue = handle.state::<Mutex<OutputQueue>>();
+    let mut output_queue = output_queue.lock().unwrap();
+    output_queue.push_leds(led_report(&leds));
+
+    if display_config.enabled {
+        let lines = render_display(
+            &display_config,
+            application_profile
+                .as_ref()
+                .map(|(name, profile)| (name.as_str(), profile)),
+            &modifiers,
+        );
+        output_queue.push_display(display_reports(&lines));
+    }
+}
+
+fn get_application_profile(
+    handle: &tauri::AppHandle,
+    window_title: &str,
+) -> Option<(String, ApplicationProfile)> {
+    let state_app_config = handle.state::<Mutex<AppConfig>>();
+    let state_app_config = state_app_config.lock().unwrap();
+
+    state_app_config
+        .get_application_profile(window_title)
+        .map(|(name, profile)| (name.clone(), profile.clone()))
 }
 
 fn track_active_window(handle: &tauri::AppHandle) {
@@ -137,12 +438,25 @@ fn track_active_window(handle: &tauri::AppHandle) {
                     // Update the current window
 
                     println!("Current window: {}", current_window.title);
-                    *state_current_window = current_window.clone();
+                    let previous_window =
+                        std::mem::replace(&mut *state_current_window, current_window.clone());
+                    drop(state_current_window);
+
+                    // Keys held for the previous profile won't be released by the new one
+                    let previous_profile = get_application_profile(handle, &previous_window.title)
+                        .map(|(name, _)| name);
+                    let current_profile = get_application_profile(handle, &current_window.title)
+                        .map(|(name, _)| name);
+                    if previous_profile != current_profile {
+                        reset_macropad(handle);
+                    }
 
                     // Emit an event to notify the frontend
                     handle
                         .emit("active-window-changed", current_window)
                         .unwrap();
+
+                    refresh_outputs(handle);
                 }
             }
             Err(e) => {
@@ -157,7 +471,7 @@ fn track_active_window(handle: &tauri::AppHandle) {
 fn get_current_window_windows() -> Result<CurrentWindow> {
     unsafe {
         let hwnd: HWND = GetForegroundWindow();
-        if hwnd.0 == std::ptr::null_mut() {
+        if hwnd.0.is_null() {
             println!("Failed to get foreground window");
             anyhow::bail!("Failed to get foreground window");
         }
@@ -182,7 +496,7 @@ fn get_current_window_windows() -> Result<CurrentWindow> {
                 });
             }
         };
-        if h_process.0 == std::ptr::null_mut() {
+        if h_process.0.is_null() {
             return Ok(CurrentWindow {
                 title,
                 app_name: "".to_string(),
@@ -194,10 +508,10 @@ fn get_current_window_windows() -> Result<CurrentWindow> {
         let len = K32GetModuleBaseNameW(h_process, None, &mut exe_name);
         let exe_name = String::from_utf16_lossy(&exe_name[..len as usize]);
 
-        return Ok(CurrentWindow {
+        Ok(CurrentWindow {
             title,
             app_name: exe_name,
-        });
+        })
     }
 }
 
@@ -211,75 +525,19 @@ fn listen_hid(handle: &tauri::AppHandle) {
                 && device_info.usage_page() == USAGE_PAGE
                 && device_info.usage() == USAGE
             {
-                if let Ok(device) = device_info.open_device(&api) {
-                    device.set_blocking_mode(false).unwrap();
-
-                    let mut buf: [u8; 2] = [0u8; 2]; // Buffer to hold the incoming data
-                    loop {
-                        match device.read(&mut buf[..]) {
-                            Ok(0) => {
-                                // No data read
-                                // Sleep for a short duration to avoid busy-waiting
-                                std::thread::sleep(std::time::Duration::from_millis(1));
-                            }
-                            Ok(n_bytes) => {
-                                println!("Read: {:?}", &buf[..n_bytes]);
-
-                                let application_profile = {
-                                    let state_app_config = handle.state::<Mutex<AppConfig>>();
-                                    let state_app_config = state_app_config.lock().unwrap();
-                                    let state_current_window =
-                                        handle.state::<Mutex<CurrentWindow>>();
-                                    let state_current_window = state_current_window.lock().unwrap();
-
-                                    // Iterate over all profiles and match regex against window title
-                                    // First matching profile is taken
-                                    state_app_config.application_profiles.iter().find_map(
-                                        |(pattern, profile)| {
-                                            if let Ok(re) = Regex::new(pattern) {
-                                                if re.is_match(&state_current_window.title) {
-                                                    Some(profile.clone())
-                                                } else {
-                                                    None
-                                                }
-                                            } else {
-                                                None
-                                            }
-                                        },
-                                    )
-                                };
-
-                                let macropad_state = handle.state::<Mutex<MacropadState>>();
-                                let mut macropad_state = macropad_state.lock().unwrap();
-
-                                let (new_macropad_state, action) =
-                                    handle_report(macropad_state.clone(), buf);
-
-                                let enigo = handle.state::<Mutex<Enigo>>();
-                                let mut enigo = enigo.lock().unwrap();
-                                perform_action(
-                                    handle,
-                                    &mut *enigo,
-                                    &application_profile,
-                                    macropad_state.clone(),
-                                    action,
-                                );
-
-                                // Update the macropad state
-                                *macropad_state = new_macropad_state;
-                            }
-                            Err(e) => {
-                                // TODO: Continue on recoverable error, break on unrecoverable error, e.g disconnected device
-                                eprintln!(
-                                    "Failed to read from device: VID: 0x{:04x}, PID: 0x{:04x}, Error: {}",
-                                    device_info.vendor_id(),
-                                    device_info.product_id(),
-                                    e
-                                );
-                                break;
-                            }
-                        }
-                    }
+                if let Ok(mut transport) = device_info
+                    .open_device(&api)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|device| HidTransport::new(device, device_info))
+                {
+                    let e = run_device(handle, &mut transport);
+                    // TODO: Continue on recoverable error, break on unrecoverable error, e.g disconnected device
+                    eprintln!(
+                        "Failed to read from device: VID: 0x{:04x}, PID: 0x{:04x}, Error: {}",
+                        device_info.vendor_id(),
+                        device_info.product_id(),
+                        e
+                    );
                 } else {
                     eprintln!(
                         "Failed to open device: VID: 0x{:04x}, PID: 0x{:04x}",
@@ -293,149 +551,714 @@ fn listen_hid(handle: &tauri::AppHandle) {
     }
 }
 
+fn set_device_info(handle: &tauri::AppHandle, device_info: Option<DeviceInfo>) {
+    let state_device_info = handle.state::<Mutex<Option<DeviceInfo>>>();
+    let mut state_device_info = state_device_info.lock().unwrap();
+    *state_device_info = device_info;
+}
+
+// Runs reports from a connected device through to actions until reading from it fails
+fn run_device(handle: &tauri::AppHandle, transport: &mut impl Transport) -> anyhow::Error {
+    let device_info = transport.device_info();
+    println!("Device connected: {:?}", device_info);
+    if !device_info.supported {
+        eprintln!("Unsupported firmware: {:?}", device_info.firmware);
+    }
+    set_device_info(handle, Some(device_info.clone()));
+    handle.emit("device-connected", device_info).unwrap();
+
+    let step_counts = transport.step_counts();
+    let mut report_filter = {
+        let state_app_config = handle.state::<Mutex<AppConfig>>();
+        let state_app_config = state_app_config.lock().unwrap();
+        ReportFilter::new(state_app_config.debounce.clone(), step_counts)
+    };
+
+    // Anything queued was meant for the previous device
+    {
+        let output_queue = handle.state::<Mutex<OutputQueue>>();
+        let mut output_queue = output_queue.lock().unwrap();
+        output_queue.reset();
+    }
+    refresh_outputs(handle);
+
+    // Reports are recorded before filtering so traces show what the device actually sent
+    let mut trace = match std::env::var(TRACE_ENV) {
+        Ok(path) => match TraceWriter::open(&path, step_counts) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                eprintln!("Failed to open trace {}: {}", path, e);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    let mut buf = [0u8; 64]; // Buffer to hold the incoming data
+    let e = loop {
+        let output_reports = {
+            let output_queue = handle.state::<Mutex<OutputQueue>>();
+            let mut output_queue = output_queue.lock().unwrap();
+            output_queue.drain()
+        };
+        for report in output_reports {
+            if let Err(e) = transport.write(&report) {
+                eprintln!("Failed to write to device: {}", e);
+            }
+        }
+
+        match transport.read(&mut buf[..]) {
+            Ok(0) => {
+                // No data read
+                // Release changes the filter was holding back once they have settled
+                if let Some(report) = report_filter.poll(std::time::Instant::now()) {
+                    process_report(handle, &report, step_counts);
+                }
+                // Sleep for a short duration to avoid busy-waiting
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            Ok(n_bytes) => {
+                println!("Read: {:?}", &buf[..n_bytes]);
+
+                if let Some(trace) = &mut trace {
+                    if let Err(e) = trace.record(&buf[..n_bytes]) {
+                        eprintln!("Failed to record report: {}", e);
+                    }
+                }
+
+                if let Some(report) =
+                    report_filter.filter(&buf[..n_bytes], std::time::Instant::now())
+                {
+                    process_report(handle, &report, step_counts);
+                }
+            }
+            Err(e) => break e,
+        }
+    };
+
+    // Release reports for anything held will never arrive from a disconnected device
+    reset_macropad(handle);
+
+    set_device_info(handle, None);
+    handle.emit("device-disconnected", ()).unwrap();
+
+    e
+}
+
+fn process_report(handle: &tauri::AppHandle, report: &[u8], step_counts: Option<bool>) {
+    let application_profile =
+        get_application_profile(handle, &get_window_title(handle)).map(|(_, profile)| profile);
+
+    {
+        let macropad_state = handle.state::<Mutex<MacropadState>>();
+        let mut macropad_state = macropad_state.lock().unwrap();
+
+        let (new_macropad_state, action, steps) = handle_report_at(
+            *macropad_state,
+            report,
+            step_counts,
+            std::time::Instant::now(),
+        );
+
+        perform_action(handle, &application_profile, *macropad_state, action, steps);
+
+        // Update the macropad state
+        *macropad_state = new_macropad_state;
+    }
+
+    // Layers and labels follow the held modifiers
+    refresh_outputs(handle);
+}
+
+// For sources which only have buttons, such as keyboards
+// These run alongside the pad so don't reset anything when they go away
+fn process_button(handle: &tauri::AppHandle, id: u8, pressed: bool) {
+    let application_profile =
+        get_application_profile(handle, &get_window_title(handle)).map(|(_, profile)| profile);
+
+    {
+        let macropad_state = handle.state::<Mutex<MacropadState>>();
+        let mut macropad_state = macropad_state.lock().unwrap();
+
+        let (new_macropad_state, action) =
+            handle_button(*macropad_state, id, pressed, std::time::Instant::now());
+
+        perform_action(handle, &application_profile, *macropad_state, action, 1);
+
+        *macropad_state = new_macropad_state;
+    }
+
+    refresh_outputs(handle);
+}
+
+// For sources which turn encoders without sending pad reports, such as MIDI controllers
+fn process_turn(handle: &tauri::AppHandle, id: u8, delta: i8) {
+    let application_profile =
+        get_application_profile(handle, &get_window_title(handle)).map(|(_, profile)| profile);
+
+    {
+        let macropad_state = handle.state::<Mutex<MacropadState>>();
+        let mut macropad_state = macropad_state.lock().unwrap();
+
+        let (new_macropad_state, action, steps) =
+            handle_turn(*macropad_state, id, delta, std::time::Instant::now());
+
+        perform_action(handle, &application_profile, *macropad_state, action, steps);
+
+        *macropad_state = new_macropad_state;
+    }
+
+    refresh_outputs(handle);
+}
+
+// Reads a device running alongside the pad, such as a MIDI control surface, until it fails
+// Its reports are decoded against its own state and only the changes are passed on, so it can't release what the pad holds
+// Whatever it held is released when it goes away
+fn run_extra_device(handle: &tauri::AppHandle, transport: &mut impl Transport) -> anyhow::Error {
+    println!("Extra device connected: {:?}", transport.device_info());
+    let step_counts = transport.step_counts();
+    let mut device_state = MacropadState::default();
+
+    let mut buf = [0u8; 64];
+    let e = loop {
+        match transport.read(&mut buf[..]) {
+            Ok(0) => std::thread::sleep(std::time::Duration::from_millis(1)),
+            Ok(n_bytes) => {
+                let (new_device_state, action, steps) = handle_report_at(
+                    device_state,
+                    &buf[..n_bytes],
+                    step_counts,
+                    std::time::Instant::now(),
+                );
+                device_state = new_device_state;
+
+                let steps = steps.min(i8::MAX as u8) as i8;
+                match action {
+                    Action::ButtonPress { id } => process_button(handle, id, true),
+                    Action::ButtonRelease { id } => process_button(handle, id, false),
+                    Action::EncoderIncrement { id } => process_turn(handle, id, steps),
+                    Action::EncoderDecrement { id } => process_turn(handle, id, -steps),
+                    _ => {}
+                }
+            }
+            Err(e) => break e,
+        }
+    };
+
+    for (id, state) in device_state.buttons.iter().enumerate() {
+        if let ButtonState::Held { .. } = state {
+            process_button(handle, id as u8, false);
+        }
+    }
+    e
+}
+
+// Resolves the action to a command and queues it for the executor
 fn perform_action(
     handle: &tauri::AppHandle,
-    enigo: &mut Enigo,
     application_profile: &Option<ApplicationProfile>,
     macropad_state: MacropadState,
     action: Action,
+    steps: u8,
 ) {
     if application_profile.is_none() {
         return;
     }
     let profile = application_profile.as_ref().unwrap();
 
-    // Create a set of held button ids
-    let mut modifiers: HashSet<u8> = macropad_state
-        .buttons
-        .iter()
-        .enumerate()
-        .filter_map(|(id, state)| {
-            if let ButtonState::Held { .. } = state {
-                Some(id as u8)
-            } else {
-                None
-            }
-        })
-        .collect();
+    // Encoder decrements step cycles backwards
+    let reverse = matches!(action, Action::EncoderDecrement { .. });
+    let resolution = {
+        let pressed_combinations = handle.state::<Mutex<PressedCombinations>>();
+        let mut pressed_combinations = pressed_combinations.lock().unwrap();
+        resolve_action(
+            profile,
+            &macropad_state,
+            &mut pressed_combinations,
+            action,
+            steps,
+            std::time::Instant::now(),
+        )
+    };
+    let resolution = match resolution {
+        Some(resolution) => resolution,
+        None => return,
+    };
+    let command = match resolution.command {
+        Some(command) => command,
+        None => return,
+    };
+
+    let binding = resolution.key_combination;
+    let job = if resolution.release {
+        Job::Release { command, binding }
+    } else {
+        Job::Run {
+            command,
+            binding: Some(binding),
+            repetitions: resolution.repetitions,
+            reverse,
+        }
+    };
+
+    queue_job(handle, job);
+}
+
+// Hands the job to the executor, starting a thread for it if it doesn't wait in the queue
+fn queue_job(handle: &tauri::AppHandle, job: Job) {
+    let task = {
+        let state_app_config = handle.state::<Mutex<AppConfig>>();
+        let state_app_config = state_app_config.lock().unwrap();
+        let executor = handle.state::<Mutex<Executor>>();
+        let mut executor = executor.lock().unwrap();
+        executor.push(job, &state_app_config.macros)
+    };
 
-    if let Action::ButtonRelease { id } = action {
-        modifiers.remove(&id);
+    if let Some(task) = task {
+        let task_handle = handle.clone();
+        std::thread::spawn(move || {
+            run_task(&task_handle, task);
+        });
     }
+}
 
-    let modifiers = if modifiers.is_empty() {
-        None
-    } else {
-        Some(modifiers)
+// Runs queued commands until the app exits
+fn run_executor(handle: &tauri::AppHandle, tasks: Receiver<Task>) {
+    for task in tasks {
+        run_task(handle, task);
+    }
+}
+
+// The injector is only locked for each key, so parallel commands interleave and handling reports never waits on a running macro
+// Releases of the command's binding which arrived while it ran are run after it, on the same thread
+fn run_task(handle: &tauri::AppHandle, task: Task) {
+    run_job(handle, &task.handle, &task.job);
+
+    let releases = {
+        let executor = handle.state::<Mutex<Executor>>();
+        let mut executor = executor.lock().unwrap();
+        executor.finish(&task.handle)
     };
+    for release in releases.iter() {
+        run_job(handle, &MacroHandle::default(), release);
+    }
+}
 
-    // Handle actions not in profile
-    match action {
-        Action::ButtonRelease { id } => {
-            // Retrieve inverse action
-            let key_combination = KeyCombination {
-                modifiers,
-                action: Action::ButtonPress { id },
-            };
-            if let Some(command) = profile.get_binding(&key_combination) {
-                if let Some(_) = command.radial_menu_items {
-                    handle.emit("hide-radial-menu", ()).unwrap();
-                } else if let Some(operations) = command.operations {
-                    let mut released_keys = HashSet::new();
-                    for operation in operations.iter().rev() {
-                        match operation {
-                            Operation::KeyRelease { key } => {
-                                released_keys.insert(key.clone());
-                            }
-                            Operation::KeyPress { key } => {
-                                if released_keys.contains(key) {
-                                    released_keys.remove(key);
-                                    continue;
-                                }
-                                println!("Releasing key: {}", key);
-                                enigo
-                                    .key(key_to_enigo_key(&key), Direction::Release)
-                                    .unwrap();
-                            }
-                            _ => {}
-                        }
-                    }
+// A panicking command is logged, so it can't take the executor thread or its schedule down with it
+fn run_job(handle: &tauri::AppHandle, macro_handle: &MacroHandle, job: &Job) {
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| match job {
+        Job::Run {
+            command,
+            repetitions,
+            reverse,
+            ..
+        } => {
+            for _ in 0..*repetitions {
+                if macro_handle.is_cancelled() {
+                    break;
                 }
+                handle_command(handle, macro_handle, command, *reverse);
             }
-            return;
         }
-        _ => {}
+        Job::Release { command, .. } => release_command(handle, command),
+    }));
+    if result.is_err() {
+        eprintln!("Command panicked: {:?}", job);
     }
+}
 
-    let key_combination = KeyCombination { modifiers, action };
-    if let Some(command) = profile.get_binding(&key_combination) {
-        handle_command(handle, enigo, &command);
+fn release_command(handle: &tauri::AppHandle, command: &Command) {
+    if command.radial_menu_items.is_some() {
+        handle.emit("hide-radial-menu", ()).unwrap();
+    } else if let Some(operations) = &command.operations {
+        let keys = {
+            let state_app_config = handle.state::<Mutex<AppConfig>>();
+            let state_app_config = state_app_config.lock().unwrap();
+            held_keys(operations, &state_app_config.macros)
+                .into_iter()
+                .map(str::to_string)
+                .collect::<Vec<String>>()
+        };
+        for key in keys {
+            let key = match parse_key(&key) {
+                Some(key) => key,
+                None => {
+                    eprintln!("Unknown key: {}", key);
+                    continue;
+                }
+            };
+            println!("Releasing key: {:?}", key);
+            with_injector(handle, |injector| injector.release_held(key));
+        }
     }
 }
 
-fn handle_command(handle: &tauri::AppHandle, enigo: &mut Enigo, command: &Command) {
+fn handle_command(
+    handle: &tauri::AppHandle,
+    macro_handle: &MacroHandle,
+    command: &Command,
+    reverse: bool,
+) {
     if let Some(radial_menu_items) = &command.radial_menu_items {
         show_radial_menu(handle, radial_menu_items);
+    } else if let Some(toggle) = &command.toggle {
+        let on = {
+            let state_toggles = handle.state::<Mutex<ToggleStates>>();
+            let mut state_toggles = state_toggles.lock().unwrap();
+            let on = state_toggles.flip(&toggle.id);
+            if let Err(e) = save_toggles(&state_toggles) {
+                eprintln!("Failed to save toggles: {}", e);
+            }
+            on
+        };
+
+        println!("Toggle {}: {}", toggle.id, if on { "on" } else { "off" });
+        handle
+            .emit(
+                "toggle-changed",
+                events::ToggleChanged {
+                    id: toggle.id.clone(),
+                    on,
+                },
+            )
+            .unwrap();
+        refresh_outputs(handle);
+
+        let operations = if on { &toggle.on } else { &toggle.off };
+        run_operations(handle, macro_handle, operations);
+    } else if let Some(cycle) = &command.cycle {
+        let index = {
+            let cycle_states = handle.state::<Mutex<CycleStates>>();
+            let mut cycle_states = cycle_states.lock().unwrap();
+            cycle_states.step(
+                &cycle.id,
+                cycle.steps.len(),
+                reverse,
+                cycle.reset_after_ms.map(std::time::Duration::from_millis),
+                std::time::Instant::now(),
+            )
+        };
+
+        if let Some(index) = index {
+            println!("Cycle {}: step {}", cycle.id, index);
+            run_operations(handle, macro_handle, &cycle.steps[index]);
+        }
     } else if let Some(operations) = &command.operations {
-        for operation in operations {
-            handle_operation(enigo, operation.clone());
+        run_operations(handle, macro_handle, operations);
+    }
+}
+
+fn run_operations(handle: &tauri::AppHandle, macro_handle: &MacroHandle, operations: &[Operation]) {
+    for operation in operations {
+        if macro_handle.is_cancelled() {
+            return;
         }
+        handle_operation(handle, macro_handle, operation.clone(), 0);
     }
 }
 
-fn show_radial_menu(handle: &tauri::AppHandle, items: &Vec<RadialMenuItem>) {
+fn show_radial_menu(handle: &tauri::AppHandle, items: &[RadialMenuItem]) {
     let enigo = Enigo::new(&Settings::default()).unwrap();
     let mouse_location = enigo.location().unwrap();
 
     let event = events::ShowRadialMenu {
         location: mouse_location,
-        items: items.iter().map(|item| (item).clone()).collect(),
+        items: items.to_vec(),
     };
 
     println!("Emitting radial menu event: {:?}", event);
     handle.emit("show-radial-menu", event).unwrap();
 }
 
-fn handle_operation(enigo: &mut Enigo, operation: Operation) {
+// `depth` is the number of macros the operation was called through
+fn handle_operation(
+    handle: &tauri::AppHandle,
+    macro_handle: &MacroHandle,
+    operation: Operation,
+    depth: usize,
+) {
     match operation {
         Operation::KeyTap { key } => {
             println!("Tapping key: {}", key);
-            enigo.key(key_to_enigo_key(&key), Direction::Click).unwrap();
+            inject_key(handle, &key, Direction::Click);
         }
         Operation::KeyPress { key } => {
             println!("Pressing key: {}", key);
-            enigo.key(key_to_enigo_key(&key), Direction::Press).unwrap();
+            inject_key(handle, &key, Direction::Press);
         }
         Operation::KeyRelease { key } => {
             println!("Releasing key: {}", key);
-            enigo
-                .key(key_to_enigo_key(&key), Direction::Release)
-                .unwrap();
+            inject_key(handle, &key, Direction::Release);
+        }
+        Operation::Shortcut { keys } => {
+            println!("Shortcut: {}", keys);
+            match parse_shortcut(&keys) {
+                Ok(keys) => with_injector(handle, |injector| injector.shortcut(&keys)),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Operation::TypeText { text } => {
+            println!("Typing: {}", text);
+            let context = {
+                let current_window = handle.state::<Mutex<CurrentWindow>>();
+                let current_window = current_window.lock().unwrap();
+                TemplateContext {
+                    now: chrono::Local::now(),
+                    window_title: current_window.title.clone(),
+                    app_name: current_window.app_name.clone(),
+                }
+            };
+            match render_template(&text, &context) {
+                Ok(text) => with_injector(handle, |injector| injector.text(&text)),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Operation::MouseClick { button } => {
+            println!("Clicking mouse button: {:?}", button);
+            with_injector(handle, |injector| {
+                injector.sink.button(button.into(), Direction::Click)
+            });
+        }
+        Operation::MouseMove { x, y, relative } => {
+            println!("Moving mouse: {}, {}", x, y);
+            with_injector(handle, |injector| {
+                injector.sink.move_mouse(x, y, mouse_coordinate(relative))
+            });
+        }
+        Operation::MouseDrag {
+            button,
+            x,
+            y,
+            relative,
+        } => {
+            println!("Dragging mouse: {}, {}", x, y);
+            with_injector(handle, |injector| {
+                injector.drag(button.into(), x, y, mouse_coordinate(relative))
+            });
+        }
+        Operation::Scroll { axis, amount } => {
+            println!("Scrolling {:?}: {}", axis, amount);
+            with_injector(handle, |injector| injector.sink.scroll(amount, axis.into()));
+        }
+        Operation::Run {
+            program,
+            args,
+            cwd,
+            env,
+            wait,
+        } => {
+            println!("Running: {} {:?}", program, args);
+            let launch_config = get_launch_config(handle);
+            let operation = format!("Run {}", program);
+            if let Err(e) = check_allowed(&launch_config.allow_list, &program) {
+                log_macro(handle, operation, Err(e));
+                return;
+            }
+
+            let command = build_command(&program, &args, &cwd, &env);
+            if wait {
+                let timeout = std::time::Duration::from_millis(launch_config.timeout_ms);
+                let result = run_and_wait(command, timeout, macro_handle);
+                log_macro(handle, operation, result.map(Some));
+            } else {
+                let log_handle = handle.clone();
+                let log_operation = operation.clone();
+                if let Err(e) = spawn(command, move |result| {
+                    log_macro(&log_handle, log_operation, result.map(Some));
+                }) {
+                    log_macro(handle, operation, Err(e));
+                }
+            }
+        }
+        Operation::OpenUrl { url } => {
+            println!("Opening URL: {}", url);
+            let result = check_url(&url).and_then(|_| Ok(open::that_detached(&url)?));
+            log_macro(handle, format!("Open {}", url), result.map(|_| None));
+        }
+        Operation::OpenPath { path } => {
+            println!("Opening path: {}", path);
+            let launch_config = get_launch_config(handle);
+            let result = check_allowed(&launch_config.allow_list, &path)
+                .and_then(|_| Ok(open::that_detached(&path)?));
+            log_macro(handle, format!("Open {}", path), result.map(|_| None));
+        }
+        Operation::ClipboardSet { text } => {
+            println!("Setting clipboard");
+            with_clipboard(handle, |clipboard| clipboard.set_text(&text));
+        }
+        Operation::ClipboardSave {} => {
+            println!("Saving clipboard");
+            with_clipboard(handle, |clipboard| clipboard.save());
+        }
+        Operation::ClipboardRestore {} => {
+            println!("Restoring clipboard");
+            with_clipboard(handle, |clipboard| clipboard.restore());
+        }
+        Operation::ClipboardTransform { kind } => {
+            println!("Transforming clipboard: {:?}", kind);
+            with_clipboard(handle, |clipboard| {
+                let text = apply_transform(&clipboard.get_text()?, &kind)?;
+                clipboard.set_text(&text)
+            });
         }
         Operation::Delay { ms } => {
-            std::thread::sleep(std::time::Duration::from_millis(ms));
+            macro_handle.sleep(std::time::Duration::from_millis(ms));
         }
         Operation::Repeat { times, operations } => {
             for _ in 0..times {
                 for operation in operations.clone() {
-                    handle_operation(enigo, operation);
+                    if macro_handle.is_cancelled() {
+                        return;
+                    }
+                    handle_operation(handle, macro_handle, operation, depth);
+                }
+            }
+        }
+        Operation::If {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let holds = condition.evaluate(&get_condition_context(handle));
+            println!("Condition {:?}: {}", condition, holds);
+            for operation in if holds { then } else { otherwise } {
+                if macro_handle.is_cancelled() {
+                    return;
                 }
+                handle_operation(handle, macro_handle, operation, depth);
             }
         }
+        Operation::SetVariable { name, value } => {
+            println!("Setting variable {} to {:?}", name, value);
+            let variables = handle.state::<Mutex<Variables>>();
+            let mut variables = variables.lock().unwrap();
+            variables.values.insert(name, value);
+        }
+        Operation::CallMacro { name } => {
+            // Macros calling each other in a loop would otherwise never end
+            if depth >= MAX_MACRO_DEPTH {
+                eprintln!("Macro {} nested too deeply, stopping", name);
+                return;
+            }
+
+            let operations = {
+                let state_app_config = handle.state::<Mutex<AppConfig>>();
+                let state_app_config = state_app_config.lock().unwrap();
+                state_app_config.macros.get(&name).cloned()
+            };
+            let operations = match operations {
+                Some(operations) => operations,
+                None => {
+                    eprintln!("Unknown macro: {}", name);
+                    return;
+                }
+            };
+
+            println!("Calling macro: {}", name);
+            for operation in operations {
+                if macro_handle.is_cancelled() {
+                    return;
+                }
+                handle_operation(handle, macro_handle, operation, depth + 1);
+            }
+        }
+        Operation::AbortMacros {} => abort_all_macros(handle),
         _ => {
             println!("Unsupported operation: {operation:?}");
         }
     }
 }
 
-fn key_to_enigo_key(key: &str) -> Key {
-    match key.to_uppercase().as_str() {
-        "ESC" => return Key::Escape,
-        "DEL" => return Key::Delete,
-        "SHIFT" => return Key::Shift,
-        "CTRL" => return Key::Control,
-        "ALT" => return Key::Alt,
-        "META" => return Key::Meta,
-        key => Key::Unicode(key.to_lowercase().chars().next().unwrap()),
+fn with_clipboard(handle: &tauri::AppHandle, f: impl FnOnce(&mut ClipboardState) -> Result<()>) {
+    let clipboard = handle.state::<Mutex<ClipboardState>>();
+    let mut clipboard = clipboard.lock().unwrap();
+    if let Err(e) = f(&mut clipboard) {
+        eprintln!("Clipboard operation failed: {}", e);
+    }
+}
+
+fn get_launch_config(handle: &tauri::AppHandle) -> LaunchConfig {
+    let state_app_config = handle.state::<Mutex<AppConfig>>();
+    let state_app_config = state_app_config.lock().unwrap();
+    state_app_config.launch.clone()
+}
+
+// Output is only captured from programs the macro waited for
+fn log_macro(handle: &tauri::AppHandle, operation: String, result: Result<Option<RunOutput>>) {
+    let log = match result {
+        Ok(output) => {
+            let output = output.unwrap_or(RunOutput {
+                exit_code: None,
+                stdout: String::new(),
+                stderr: String::new(),
+            });
+            events::MacroLog {
+                operation,
+                exit_code: output.exit_code,
+                stdout: output.stdout,
+                stderr: output.stderr,
+                error: None,
+            }
+        }
+        Err(e) => events::MacroLog {
+            operation,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            error: Some(e.to_string()),
+        },
+    };
+
+    println!("Macro log: {:?}", log);
+    handle.emit("macro-log", log).unwrap();
+}
+
+fn mouse_coordinate(relative: bool) -> Coordinate {
+    if relative {
+        Coordinate::Rel
+    } else {
+        Coordinate::Abs
+    }
+}
+
+// Commands from the config have already been validated, but the frontend can send anything
+fn inject_key(handle: &tauri::AppHandle, key: &str, direction: Direction) {
+    let key = match parse_key(key) {
+        Some(key) => key,
+        None => {
+            eprintln!("Unknown key: {}", key);
+            return;
+        }
+    };
+
+    with_injector(handle, |injector| injector.key(key, direction));
+}
+
+// A macro that panicked while sending input leaves the lock poisoned, the injector itself is still usable
+fn lock_injector(handle: &tauri::AppHandle) -> MutexGuard<'_, Injector> {
+    handle
+        .state::<Mutex<Injector>>()
+        .inner()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+// Locks the injector for a single input, logging rather than panicking if sending it fails
+fn with_injector(handle: &tauri::AppHandle, f: impl FnOnce(&mut Injector) -> InputResult<()>) {
+    let mut injector = lock_injector(handle);
+    if let Err(e) = f(&mut injector) {
+        eprintln!("Failed to send input: {}", e);
     }
 }
+
+// Stops every command, including the one calling it, and lets go of the keys they held
+fn abort_all_macros(handle: &tauri::AppHandle) {
+    {
+        let executor = handle.state::<Mutex<Executor>>();
+        let mut executor = executor.lock().unwrap();
+        executor.abort_all();
+    }
+
+    lock_injector(handle).release_all();
+    turn_off_held_toggles(handle);
+}
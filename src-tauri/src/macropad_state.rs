@@ -1,3 +1,15 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::config::{Action, Modifier};
+
+// How long after its last detent an encoder still counts as mid-rotation when used as a modifier
+pub const ENCODER_MODIFIER_WINDOW: Duration = Duration::from_millis(300);
+// The pad's buttons come first, the rest are for other sources such as keyboards
+pub const BUTTON_COUNT: usize = 32;
+// Encoders and their push switches
+pub const ENCODER_COUNT: usize = 1;
+
 #[derive(Clone, Copy, Debug)]
 pub enum ButtonState {
   None,
@@ -6,8 +18,122 @@ pub enum ButtonState {
   }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct EncoderTurn {
+  pub at: Instant,
+  // 1 for increments, -1 for decrements
+  pub direction: i8,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CyclePosition {
+  index: usize,
+  at: Instant,
+}
+
+// Which step each cycle command last ran, by cycle id
+#[derive(Clone, Debug, Default)]
+pub struct CycleStates {
+  positions: HashMap<String, CyclePosition>,
+}
+
+impl CycleStates {
+  // Moves the cycle one step, wrapping around, and returns the step to run
+  // A cycle that hasn't moved within `reset_after` starts over from the first step, or the last when going backwards
+  pub fn step(&mut self, id: &str, len: usize, reverse: bool, reset_after: Option<Duration>, now: Instant) -> Option<usize> {
+    if len == 0 {
+      return None;
+    }
+
+    let current = self.positions.get(id).filter(|position| match reset_after {
+      Some(reset_after) => now.duration_since(position.at) <= reset_after,
+      None => true,
+    });
+    // The steps may have changed since the cycle last moved
+    let index = match (current, reverse) {
+      (None, false) => 0,
+      (None, true) => len - 1,
+      (Some(position), false) => (position.index + 1) % len,
+      (Some(position), true) => (position.index % len + len - 1) % len,
+    };
+
+    self.positions.insert(id.to_string(), CyclePosition { index, at: now });
+    Some(index)
+  }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct MacropadState {
-  pub buttons: [ButtonState; 12],
-  pub encoders: [i8; 1],
-}
\ No newline at end of file
+  pub buttons: [ButtonState; BUTTON_COUNT],
+  pub encoders: [i8; ENCODER_COUNT],
+  pub encoder_switches: [ButtonState; ENCODER_COUNT],
+  // When and which way each encoder last moved
+  pub encoder_turns: [Option<EncoderTurn>; ENCODER_COUNT],
+}
+
+impl Default for MacropadState {
+  fn default() -> Self {
+    MacropadState {
+      buttons: [ButtonState::None; BUTTON_COUNT],
+      encoders: [0; ENCODER_COUNT],
+      encoder_switches: [ButtonState::None; ENCODER_COUNT],
+      encoder_turns: [None; ENCODER_COUNT],
+    }
+  }
+}
+
+impl MacropadState {
+  // Inputs that are currently active and can modify the action
+  pub fn get_modifiers(&self, action: &Action, now: Instant) -> HashSet<Modifier> {
+    let mut modifiers = HashSet::new();
+
+    for (id, state) in self.buttons.iter().enumerate() {
+      if let ButtonState::Held { .. } = state {
+        modifiers.insert(Modifier::Button(id as u8));
+      }
+    }
+
+    for (id, state) in self.encoder_switches.iter().enumerate() {
+      if let ButtonState::Held { .. } = state {
+        modifiers.insert(Modifier::EncoderPush(id as u8));
+      }
+    }
+
+    for (id, turn) in self.encoder_turns.iter().enumerate() {
+      match turn {
+        Some(turn) if now.duration_since(turn.at) <= ENCODER_MODIFIER_WINDOW => {
+          if turn.direction > 0 {
+            modifiers.insert(Modifier::EncoderIncrement(id as u8));
+          } else {
+            modifiers.insert(Modifier::EncoderDecrement(id as u8));
+          }
+        }
+        _ => {}
+      }
+    }
+
+    // An input can't modify its own action
+    match *action {
+      Action::ButtonRelease { id } => {
+        modifiers.remove(&Modifier::Button(id));
+      }
+      // A push is often preceded by a slight turn of the same knob
+      Action::EncoderPush { id } => {
+        modifiers.remove(&Modifier::EncoderIncrement(id));
+        modifiers.remove(&Modifier::EncoderDecrement(id));
+      }
+      Action::EncoderRelease { id } => {
+        modifiers.remove(&Modifier::EncoderPush(id));
+        modifiers.remove(&Modifier::EncoderIncrement(id));
+        modifiers.remove(&Modifier::EncoderDecrement(id));
+      }
+      Action::EncoderIncrement { id } | Action::EncoderDecrement { id } => {
+        modifiers.remove(&Modifier::EncoderIncrement(id));
+        modifiers.remove(&Modifier::EncoderDecrement(id));
+      }
+      _ => {}
+    }
+
+    modifiers
+  }
+}
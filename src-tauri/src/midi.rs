@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use regex::Regex;
+
+use crate::config::{MidiControllerMode, MidiDeviceConfig};
+use crate::device_info::DeviceInfo;
+use crate::hid::PAD_BUTTON_COUNT;
+use crate::transport::Transport;
+
+const MIDI_CLIENT_NAME: &str = "macropad-console";
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xB0;
+
+// MIDI has no disconnect notification, so the port is looked for this often
+const PORT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Turns MIDI messages into reports in the HID report format, using step counts for the encoders
+pub struct MidiMapper {
+    config: MidiDeviceConfig,
+    // Button bits of the last report
+    buttons: u16,
+    // Last value of each absolute controller
+    controller_values: HashMap<u8, u8>,
+}
+
+impl MidiMapper {
+    pub fn new(config: MidiDeviceConfig) -> Self {
+        MidiMapper {
+            config,
+            buttons: 0,
+            controller_values: HashMap::new(),
+        }
+    }
+
+    // Returns None for messages which don't change anything mapped
+    pub fn map(&mut self, message: &[u8]) -> Option<Vec<u8>> {
+        let (status, data1, data2) = match message {
+            [status, data1, data2, ..] => (*status, *data1, *data2),
+            _ => return None,
+        };
+        if let Some(channel) = self.config.channel {
+            if status & 0x0F != channel.wrapping_sub(1) {
+                return None;
+            }
+        }
+
+        let mut step_counts = vec![0];
+        match status & 0xF0 {
+            // Note on with no velocity is a note off
+            NOTE_ON if data2 > 0 => self.buttons |= self.button_bit(data1)?,
+            NOTE_ON | NOTE_OFF => self.buttons &= !self.button_bit(data1)?,
+            CONTROL_CHANGE => {
+                let controller = self.config.controllers.get(&data1)?;
+                let steps = match controller.mode {
+                    MidiControllerMode::Absolute => {
+                        // The first value only tells us where the control is
+                        let previous = self.controller_values.insert(data1, data2)?;
+                        data2 as i16 - previous as i16
+                    }
+                    MidiControllerMode::Relative if data2 < 64 => data2 as i16,
+                    MidiControllerMode::Relative => data2 as i16 - 128,
+                    MidiControllerMode::RelativeOffset => data2 as i16 - 64,
+                };
+                if steps == 0 {
+                    return None;
+                }
+
+                let encoder = controller.encoder as usize;
+                step_counts.resize(encoder + 1, 0);
+                step_counts[encoder] = steps as i8 as u8;
+            }
+            _ => return None,
+        }
+
+        let mut report = self.buttons.to_le_bytes().to_vec();
+        report.extend(step_counts);
+        Some(report)
+    }
+
+    fn button_bit(&self, note: u8) -> Option<u16> {
+        let id = *self.config.notes.get(&note)?;
+        if id as usize >= PAD_BUTTON_COUNT {
+            return None;
+        }
+        Some(1 << id)
+    }
+}
+
+pub struct MidiTransport {
+    // Messages stop arriving once this is dropped
+    _connection: MidiInputConnection<()>,
+    reports: Receiver<Vec<u8>>,
+    port_name: String,
+    checked_at: Instant,
+}
+
+impl MidiTransport {
+    // Connects to the first port matching one of the configs
+    pub fn connect(configs: &[MidiDeviceConfig]) -> Result<Option<Self>> {
+        let mut midi_in = MidiInput::new(MIDI_CLIENT_NAME)?;
+        midi_in.ignore(Ignore::All);
+
+        let mut found = None;
+        for port in midi_in.ports() {
+            let port_name = midi_in.port_name(&port)?;
+            let config = configs.iter().find(|config| {
+                Regex::new(&config.port)
+                    .map(|re| re.is_match(&port_name))
+                    .unwrap_or(false)
+            });
+            if let Some(config) = config {
+                found = Some((port, port_name, config.clone()));
+                break;
+            }
+        }
+        let (port, port_name, config) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let (sender, reports) = mpsc::channel();
+        let mut mapper = MidiMapper::new(config);
+        let connection = midi_in
+            .connect(
+                &port,
+                MIDI_CLIENT_NAME,
+                move |_, message, _| {
+                    if let Some(report) = mapper.map(message) {
+                        // The transport is gone if this fails, and the connection with it
+                        let _ = sender.send(report);
+                    }
+                },
+                (),
+            )
+            .map_err(|e| anyhow!("Failed to connect to MIDI port {}: {}", port_name, e))?;
+
+        Ok(Some(MidiTransport {
+            _connection: connection,
+            reports,
+            port_name,
+            checked_at: Instant::now(),
+        }))
+    }
+
+    fn port_exists(&self) -> Result<bool> {
+        let midi_in = MidiInput::new(MIDI_CLIENT_NAME)?;
+        Ok(midi_in
+            .ports()
+            .iter()
+            .any(|port| midi_in.port_name(port).ok().as_ref() == Some(&self.port_name)))
+    }
+}
+
+impl Transport for MidiTransport {
+    fn device_info(&self) -> DeviceInfo {
+        DeviceInfo::new(None, Some(self.port_name.clone()), None, None)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.reports.try_recv() {
+            Ok(report) => {
+                let n_bytes = report.len().min(buf.len());
+                buf[..n_bytes].copy_from_slice(&report[..n_bytes]);
+                Ok(n_bytes)
+            }
+            Err(TryRecvError::Empty) => {
+                if self.checked_at.elapsed() >= PORT_CHECK_INTERVAL {
+                    self.checked_at = Instant::now();
+                    if !self.port_exists()? {
+                        bail!("MIDI port {} disconnected", self.port_name);
+                    }
+                }
+                Ok(0)
+            }
+            Err(TryRecvError::Disconnected) => bail!("MIDI connection closed"),
+        }
+    }
+
+    // LEDs and displays are driven by output reports, which control surfaces don't understand
+    fn write(&mut self, _report: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    // Reports are built by MidiMapper
+    fn step_counts(&self) -> Option<bool> {
+        Some(true)
+    }
+}
+
+// Each device has its own connection, and runs alongside the pad
+pub fn listen_midi(handle: &tauri::AppHandle, configs: &[MidiDeviceConfig]) {
+    let threads = configs
+        .iter()
+        .cloned()
+        .map(|config| {
+            let handle = handle.clone();
+            std::thread::spawn(move || listen_midi_device(&handle, &config))
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
+
+fn listen_midi_device(handle: &tauri::AppHandle, config: &MidiDeviceConfig) {
+    loop {
+        match MidiTransport::connect(std::slice::from_ref(config)) {
+            Ok(Some(mut transport)) => {
+                let e = crate::run_extra_device(handle, &mut transport);
+                eprintln!("Failed to read from MIDI device: {}", e);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Failed to open MIDI input: {}", e);
+            }
+        }
+
+        // Wait for a control surface to be plugged in
+        std::thread::sleep(RECONNECT_INTERVAL);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::config::LedState;
+
+// Output report carrying the colour of every key's LED
+// [LED_REPORT_ID, r0, g0, b0, r1, g1, b1, ...]
+pub const LED_REPORT_ID: u8 = 0x02;
+pub const LED_COUNT: usize = 12;
+
+pub fn led_report(leds: &HashMap<u8, LedState>) -> Vec<u8> {
+    let mut report = vec![LED_REPORT_ID];
+
+    for id in 0..LED_COUNT as u8 {
+        let (r, g, b) = match leds.get(&id).copied().unwrap_or_default() {
+            LedState::Off => (0, 0, 0),
+            LedState::On => (0xFF, 0xFF, 0xFF),
+            LedState::Rgb { r, g, b } => (r, g, b),
+        };
+        report.extend([r, g, b]);
+    }
+
+    report
+}
+
+// Output reports waiting to be written by the thread that owns the device
+#[derive(Default)]
+pub struct OutputQueue {
+    reports: VecDeque<Vec<u8>>,
+    last_led_report: Option<Vec<u8>>,
+    last_display_reports: Option<Vec<Vec<u8>>>,
+}
+
+impl OutputQueue {
+    pub fn push(&mut self, report: Vec<u8>) {
+        self.reports.push_back(report);
+    }
+
+    // LED reports are only queued when the LEDs change
+    pub fn push_leds(&mut self, report: Vec<u8>) {
+        if self.last_led_report.as_ref() == Some(&report) {
+            return;
+        }
+
+        self.last_led_report = Some(report.clone());
+        self.push(report);
+    }
+
+    // Display reports are only queued when the text changes
+    pub fn push_display(&mut self, reports: Vec<Vec<u8>>) {
+        if self.last_display_reports.as_ref() == Some(&reports) {
+            return;
+        }
+
+        self.last_display_reports = Some(reports.clone());
+        self.reports.extend(reports);
+    }
+
+    pub fn drain(&mut self) -> Vec<Vec<u8>> {
+        self.reports.drain(..).collect()
+    }
+
+    // A newly connected device knows nothing of what was sent before
+    pub fn reset(&mut self) {
+        self.reports.clear();
+        self.last_led_report = None;
+        self.last_display_reports = None;
+    }
+}
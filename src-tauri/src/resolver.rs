@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::config::{Action, ApplicationProfile, Command, KeyCombination, Modifier};
+use crate::macropad_state::MacropadState;
+
+// What an action does in a profile
+#[derive(Clone, Debug)]
+pub struct Resolution {
+    // For releases, this is the press whose command is being released
+    pub key_combination: KeyCombination,
+    pub command: Option<Command>,
+    pub release: bool,
+    // Number of times the command runs, 0 for releases
+    pub repetitions: u64,
+}
+
+// Key combinations which buttons and encoder switches were resolved to when pressed, by the press action
+// Releases use these, as the modifiers may have changed while the input was held
+#[derive(Clone, Debug, Default)]
+pub struct PressedCombinations {
+    combinations: HashMap<Action, KeyCombination>,
+}
+
+impl PressedCombinations {
+    pub fn clear(&mut self) {
+        self.combinations.clear();
+    }
+}
+
+// Looks up the binding for an action, given the state of the pad before the report which caused it
+// Returns None if there is no action
+pub fn resolve_action(
+    profile: &ApplicationProfile,
+    macropad_state: &MacropadState,
+    pressed_combinations: &mut PressedCombinations,
+    action: Action,
+    steps: u8,
+    now: Instant,
+) -> Option<Resolution> {
+    if action == Action::None {
+        return None;
+    }
+
+    let modifiers = macropad_state.get_modifiers(&action, now);
+
+    // Releases aren't in the profile, they release the command bound to the press
+    let (action, release) = match action {
+        Action::ButtonRelease { id } => (Action::ButtonPress { id }, true),
+        Action::EncoderRelease { id } => (Action::EncoderPush { id }, true),
+        action => (action, false),
+    };
+
+    // How fast the encoder is being spun, in detents per second
+    let speed = match action {
+        Action::EncoderIncrement { id } | Action::EncoderDecrement { id } => macropad_state
+            .encoder_turns
+            .get(id as usize)
+            .copied()
+            .flatten()
+            .map(|turn| steps as f64 / now.duration_since(turn.at).as_secs_f64()),
+        _ => None,
+    };
+
+    let (key_combination, command) = if release {
+        match pressed_combinations.combinations.remove(&action) {
+            Some(key_combination) => {
+                let command = profile.get_binding(&key_combination);
+                (key_combination, command)
+            }
+            // Pressed before the state was reset
+            None => find_binding(profile, modifiers, action),
+        }
+    } else {
+        find_binding(profile, modifiers, action)
+    };
+
+    let pressed = matches!(
+        key_combination.action,
+        Action::ButtonPress { .. } | Action::EncoderPush { .. }
+    );
+    if pressed && !release {
+        pressed_combinations
+            .combinations
+            .insert(key_combination.action.clone(), key_combination.clone());
+    }
+
+    let repetitions = match &command {
+        Some(command) if !release => command.get_repetitions(steps, speed),
+        _ => 0,
+    };
+
+    Some(Resolution {
+        key_combination,
+        command,
+        release,
+        repetitions,
+    })
+}
+
+// Encoder turns only modify the action if there is a binding for them, as they linger after the encoder stops
+// Otherwise the action is looked up with just the held inputs
+fn find_binding(
+    profile: &ApplicationProfile,
+    modifiers: HashSet<Modifier>,
+    action: Action,
+) -> (KeyCombination, Option<Command>) {
+    let held_modifiers = modifiers
+        .iter()
+        .copied()
+        .filter(|modifier| {
+            !matches!(
+                modifier,
+                Modifier::EncoderIncrement(_) | Modifier::EncoderDecrement(_)
+            )
+        })
+        .collect::<HashSet<Modifier>>();
+
+    if held_modifiers.len() < modifiers.len() {
+        let key_combination = to_key_combination(modifiers, action.clone());
+        if let Some(command) = profile.get_binding(&key_combination) {
+            return (key_combination, Some(command));
+        }
+    }
+
+    let key_combination = to_key_combination(held_modifiers, action);
+    let command = profile.get_binding(&key_combination);
+    (key_combination, command)
+}
+
+fn to_key_combination(modifiers: HashSet<Modifier>, action: Action) -> KeyCombination {
+    KeyCombination {
+        modifiers: if modifiers.is_empty() {
+            None
+        } else {
+            Some(modifiers)
+        },
+        action,
+    }
+}
@@ -0,0 +1,220 @@
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use serialport::{SerialPort, SerialPortType};
+
+use crate::config::SerialConfig;
+use crate::device_info::DeviceInfo;
+use crate::hid::PAD_BUTTON_COUNT;
+use crate::macropad_state::ENCODER_COUNT;
+use crate::transport::Transport;
+
+// Pads send a line of text per event, or a binary frame carrying a whole report
+// "D <id>" / "U <id>"   button down / up
+// "P <id>" / "R <id>"   encoder push switch pressed / released
+// "E <id> <steps>"      encoder turned by a signed number of detents
+// [FRAME_START, length, report...]
+// Output reports are sent to the pad as binary frames
+pub const FRAME_START: u8 = 0x02;
+
+// Bits of the first two report bytes holding the 2 bit encoder value
+const ENCODER_BITS: u16 = 0b0011_0000_0000_0000;
+
+const READ_TIMEOUT: Duration = Duration::from_millis(1);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Turns the bytes read from the port into reports in the HID report format
+// Reports built from text events use step counts for the encoders
+#[derive(Default)]
+pub struct SerialParser {
+    buffer: Vec<u8>,
+    // Button and encoder switch bits of the last report
+    buttons: u16,
+}
+
+impl SerialParser {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    // Returns None until a whole event has been pushed
+    pub fn next_report(&mut self) -> Option<Result<Vec<u8>>> {
+        loop {
+            if self.buffer.first() == Some(&FRAME_START) {
+                let length = *self.buffer.get(1)? as usize;
+                if self.buffer.len() < 2 + length {
+                    return None;
+                }
+
+                let report = self.buffer.drain(..2 + length).skip(2).collect::<Vec<u8>>();
+                // Later text events continue from the buttons in the report
+                if report.len() >= 2 {
+                    self.buttons = u16::from_le_bytes([report[0], report[1]]) & !ENCODER_BITS;
+                }
+                return Some(Ok(report));
+            }
+
+            let end = self.buffer.iter().position(|byte| *byte == b'\n')?;
+            let line = self.buffer.drain(..=end).collect::<Vec<u8>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            return Some(
+                self.parse_event(line)
+                    .map_err(|e| anyhow!("Invalid serial event {:?}: {}", line, e)),
+            );
+        }
+    }
+
+    fn parse_event(&mut self, line: &str) -> Result<Vec<u8>> {
+        let button_bit = |id: &str| -> Result<u16> {
+            let id = id.parse::<u8>()?;
+            if id as usize >= PAD_BUTTON_COUNT {
+                bail!("No button {}", id);
+            }
+            Ok(1 << id)
+        };
+        let switch_bit = |id: &str| -> Result<u16> {
+            let id = id.parse::<u8>()?;
+            // Each encoder has one push switch
+            if id as usize >= ENCODER_COUNT {
+                bail!("No encoder switch {}", id);
+            }
+            Ok(1 << (14 + id))
+        };
+
+        let mut step_counts = vec![0];
+        match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["D", id] => self.buttons |= button_bit(id)?,
+            ["U", id] => self.buttons &= !button_bit(id)?,
+            ["P", id] => self.buttons |= switch_bit(id)?,
+            ["R", id] => self.buttons &= !switch_bit(id)?,
+            ["E", id, steps] => {
+                let id = id.parse::<u8>()?;
+                if id as usize >= ENCODER_COUNT {
+                    bail!("No encoder {}", id);
+                }
+                let id = id as usize;
+                step_counts.resize(id + 1, 0);
+                step_counts[id] = steps.parse::<i8>()? as u8;
+            }
+            _ => bail!("Unknown event"),
+        }
+
+        let mut report = self.buttons.to_le_bytes().to_vec();
+        report.extend(step_counts);
+        Ok(report)
+    }
+}
+
+pub struct SerialTransport {
+    port: Box<dyn SerialPort>,
+    parser: SerialParser,
+    device_info: DeviceInfo,
+}
+
+impl SerialTransport {
+    // The port should have a short timeout as reads wait for it
+    pub fn new(port: Box<dyn SerialPort>, device_info: DeviceInfo) -> Self {
+        SerialTransport {
+            port,
+            parser: SerialParser::default(),
+            device_info,
+        }
+    }
+
+    fn next_report(&mut self, buf: &mut [u8]) -> Option<usize> {
+        while let Some(report) = self.parser.next_report() {
+            match report {
+                Ok(report) => {
+                    let n_bytes = report.len().min(buf.len());
+                    buf[..n_bytes].copy_from_slice(&report[..n_bytes]);
+                    return Some(n_bytes);
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        None
+    }
+}
+
+impl Transport for SerialTransport {
+    fn device_info(&self) -> DeviceInfo {
+        self.device_info.clone()
+    }
+
+    // Reports built from serial events always have step counts
+    fn step_counts(&self) -> Option<bool> {
+        Some(true)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(n_bytes) = self.next_report(buf) {
+            return Ok(n_bytes);
+        }
+
+        let mut chunk = [0u8; 64];
+        match self.port.read(&mut chunk) {
+            Ok(n_bytes) => self.parser.push(&chunk[..n_bytes]),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(self.next_report(buf).unwrap_or(0))
+    }
+
+    fn write(&mut self, report: &[u8]) -> Result<()> {
+        let mut frame = vec![FRAME_START, report.len() as u8];
+        frame.extend(report);
+        self.port.write_all(&frame)?;
+        Ok(())
+    }
+}
+
+// USB serial ports describe the pad, other ports only have their name
+fn serial_device_info(port_name: &str) -> DeviceInfo {
+    let usb_info = serialport::available_ports()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|port| port.port_name == port_name)
+        .and_then(|port| match port.port_type {
+            SerialPortType::UsbPort(info) => Some(info),
+            _ => None,
+        });
+
+    match usb_info {
+        Some(info) => DeviceInfo::new(info.manufacturer, info.product, info.serial_number, None),
+        None => DeviceInfo::new(None, Some(port_name.to_string()), None, None),
+    }
+}
+
+fn open_serial(config: &SerialConfig) -> Result<SerialTransport> {
+    let port = serialport::new(&config.port, config.baud_rate)
+        .timeout(READ_TIMEOUT)
+        // CDC-ACM pads commonly wait for DTR before sending
+        .dtr_on_open(true)
+        .open()?;
+
+    Ok(SerialTransport::new(port, serial_device_info(&config.port)))
+}
+
+pub fn listen_serial(handle: &tauri::AppHandle, config: &SerialConfig) {
+    loop {
+        match open_serial(config) {
+            Ok(mut transport) => {
+                let e = crate::run_device(handle, &mut transport);
+                eprintln!("Failed to read from serial port {}: {}", config.port, e);
+            }
+            Err(e) => {
+                eprintln!("Failed to open serial port {}: {}", config.port, e);
+            }
+        }
+
+        // Wait for the pad to be plugged back in
+        std::thread::sleep(RECONNECT_INTERVAL);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+
+// Values for the placeholders in typed text
+pub struct TemplateContext {
+    pub now: DateTime<Local>,
+    pub window_title: String,
+    pub app_name: String,
+}
+
+// Placeholders in braces are replaced, "{{" and "}}" are literal braces
+// {date}      2024-05-01
+// {time}      13:45:00
+// {datetime}  2024-05-01 13:45:00
+// {date:...}  the date and time in a strftime format, e.g. {date:%d/%m/%Y}
+// {window}    title of the active window
+// {app}       executable name of the active window
+pub fn render_template(text: &str, context: &TemplateContext) -> Result<String> {
+    let mut output = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => bail!("Unclosed placeholder in {:?}", text),
+                    }
+                }
+                render_placeholder(&placeholder, context, &mut output)?;
+            }
+            '}' => bail!("Unmatched \"}}\" in {:?}", text),
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+fn render_placeholder(
+    placeholder: &str,
+    context: &TemplateContext,
+    output: &mut String,
+) -> Result<()> {
+    match placeholder.split_once(':') {
+        Some(("date", format)) => format_date(&context.now, format, output),
+        Some(_) => bail!("Unknown placeholder {{{}}}", placeholder),
+        None => match placeholder {
+            "date" => format_date(&context.now, "%Y-%m-%d", output),
+            "time" => format_date(&context.now, "%H:%M:%S", output),
+            "datetime" => format_date(&context.now, "%Y-%m-%d %H:%M:%S", output),
+            "window" => {
+                output.push_str(&context.window_title);
+                Ok(())
+            }
+            "app" => {
+                output.push_str(&context.app_name);
+                Ok(())
+            }
+            _ => bail!("Unknown placeholder {{{}}}", placeholder),
+        },
+    }
+}
+
+fn format_date(now: &DateTime<Local>, format: &str, output: &mut String) -> Result<()> {
+    let items = StrftimeItems::new(format).collect::<Vec<Item>>();
+    if items.contains(&Item::Error) {
+        bail!("Invalid date format {:?}", format);
+    }
+
+    write!(output, "{}", now.format_with_items(items.into_iter()))?;
+    Ok(())
+}
+
+// Checks the placeholders without the values, for config validation
+pub fn validate_template(text: &str) -> Result<()> {
+    let context = TemplateContext {
+        now: Local::now(),
+        window_title: String::new(),
+        app_name: String::new(),
+    };
+    render_template(text, &context).map(|_| ())
+}
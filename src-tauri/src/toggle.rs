@@ -0,0 +1,60 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::get_config_path;
+
+// Toggle ids to whether they are on, saved so toggles keep their state across restarts
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToggleStates {
+    pub toggles: HashMap<String, bool>,
+}
+
+impl ToggleStates {
+    // Toggles start off
+    pub fn is_on(&self, id: &str) -> bool {
+        self.toggles.get(id).copied().unwrap_or(false)
+    }
+
+    // Returns whether the toggle is now on
+    pub fn flip(&mut self, id: &str) -> bool {
+        let on = !self.is_on(id);
+        self.toggles.insert(id.to_string(), on);
+        on
+    }
+
+    // Turns the toggles off, returning the ids of those which were on
+    pub fn turn_off(&mut self, ids: &HashSet<String>) -> Vec<String> {
+        let mut turned_off = vec![];
+        for id in ids {
+            if self.is_on(id) {
+                self.toggles.insert(id.clone(), false);
+                turned_off.push(id.clone());
+            }
+        }
+        turned_off
+    }
+}
+
+pub fn get_toggles_path() -> PathBuf {
+    get_config_path().with_file_name("toggles.json")
+}
+
+pub fn load_toggles() -> Result<ToggleStates> {
+    let path = get_toggles_path();
+    if !path.exists() {
+        return Ok(ToggleStates::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_toggles(toggles: &ToggleStates) -> Result<()> {
+    let path = get_toggles_path();
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, serde_json::to_string(toggles)?)?;
+    Ok(())
+}
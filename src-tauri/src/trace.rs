@@ -0,0 +1,171 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+
+use crate::config::{Action, ApplicationProfile, DebounceConfig};
+use crate::debounce::ReportFilter;
+use crate::hid::handle_report_at;
+use crate::macropad_state::MacropadState;
+use crate::resolver::{resolve_action, PressedCombinations, Resolution};
+
+// Set to a file path to record every report read from the device to it
+pub const TRACE_ENV: &str = "MACROPAD_TRACE";
+// Starts the header line of each session, followed by "true", "false" or "unknown"
+const STEP_COUNTS_HEADER: &str = "# step counts:";
+
+// Traces have a line per report, with the milliseconds since the Unix epoch and the report as hex bytes
+// e.g. "1735689600000 10 00"
+// Recording appends, so a trace may span several sessions
+// Each session starts with a header saying whether the device's encoders send step counts, e.g. "# step counts: true"
+pub struct TraceWriter {
+    file: File,
+}
+
+impl TraceWriter {
+    // `step_counts` is what the transport reported, so replay decodes reports the same way
+    pub fn open(path: impl AsRef<Path>, step_counts: Option<bool>) -> Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let step_counts = match step_counts {
+            Some(step_counts) => step_counts.to_string(),
+            None => "unknown".to_string(),
+        };
+        writeln!(file, "{} {}", STEP_COUNTS_HEADER, step_counts)?;
+        Ok(TraceWriter { file })
+    }
+
+    pub fn record(&mut self, report: &[u8]) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        writeln!(self.file, "{} {}", timestamp, format_report(report))?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    // Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub report: Vec<u8>,
+    // From the header of the session, unknown for traces recorded without one
+    pub step_counts: Option<bool>,
+}
+
+// Blank lines and lines starting with "#" are ignored, other than step count headers
+pub fn parse_trace(trace: &str) -> Result<Vec<TraceEntry>> {
+    let mut step_counts = None;
+    let mut entries = vec![];
+    for (i, line) in trace.lines().enumerate() {
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix(STEP_COUNTS_HEADER) {
+            step_counts = match value.trim() {
+                "unknown" => None,
+                value => Some(
+                    value
+                        .parse()
+                        .map_err(|e| anyhow!("Line {}: {}", i + 1, e))?,
+                ),
+            };
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(
+            parse_trace_line(line, step_counts).map_err(|e| anyhow!("Line {}: {}", i + 1, e))?,
+        );
+    }
+    Ok(entries)
+}
+
+fn parse_trace_line(line: &str, step_counts: Option<bool>) -> Result<TraceEntry> {
+    let mut fields = line.split_whitespace();
+    let timestamp = fields.next().unwrap_or_default().parse()?;
+    Ok(TraceEntry {
+        timestamp,
+        report: parse_report(fields)?,
+        step_counts,
+    })
+}
+
+// Reports are written as space separated hex bytes, e.g. "10 00"
+pub fn format_report(report: &[u8]) -> String {
+    report
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Bytes may have a "0x" prefix
+pub fn parse_report<'a>(bytes: impl IntoIterator<Item = &'a str>) -> Result<Vec<u8>> {
+    Ok(bytes
+        .into_iter()
+        .map(|byte| u8::from_str_radix(byte.trim_start_matches("0x"), 16))
+        .collect::<Result<Vec<u8>, _>>()?)
+}
+
+// A report passed on by the debounce filter and what it resolved to
+#[derive(Clone, Debug)]
+pub struct ReplayStep {
+    pub timestamp: u64,
+    pub report: Vec<u8>,
+    pub action: Action,
+    pub resolution: Option<Resolution>,
+}
+
+// Runs a trace through the debounce filter, handle_report and the binding resolver as if it was being read from the device
+// Reports are decoded with the step count flag recorded for their session
+pub fn replay_trace(
+    entries: &[TraceEntry],
+    debounce: DebounceConfig,
+    profile: &ApplicationProfile,
+) -> Vec<ReplayStep> {
+    let start = Instant::now();
+    let first_timestamp = entries.first().map(|entry| entry.timestamp).unwrap_or(0);
+
+    let mut step_counts = entries.first().and_then(|entry| entry.step_counts);
+    let mut report_filter = ReportFilter::new(debounce.clone(), step_counts);
+    let mut macropad_state = MacropadState::default();
+    let mut pressed_combinations = PressedCombinations::default();
+    let mut steps = vec![];
+
+    for entry in entries {
+        let now = start + Duration::from_millis(entry.timestamp.saturating_sub(first_timestamp));
+        // A new filter for each device, as when reading from it
+        if entry.step_counts != step_counts {
+            step_counts = entry.step_counts;
+            report_filter = ReportFilter::new(debounce.clone(), step_counts);
+        }
+
+        // Changes the filter held back would have settled while waiting for this report
+        let reports = report_filter
+            .poll(now)
+            .into_iter()
+            .chain(report_filter.filter(&entry.report, now));
+
+        for report in reports.collect::<Vec<Vec<u8>>>() {
+            let (new_macropad_state, action, n_steps) =
+                handle_report_at(macropad_state, &report, step_counts, now);
+            let resolution = resolve_action(
+                profile,
+                &macropad_state,
+                &mut pressed_combinations,
+                action.clone(),
+                n_steps,
+                now,
+            );
+
+            steps.push(ReplayStep {
+                timestamp: entry.timestamp,
+                report,
+                action,
+                resolution,
+            });
+            macropad_state = new_macropad_state;
+        }
+    }
+
+    steps
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+
+use crate::device_info::{parse_firmware_info, DeviceInfo, FIRMWARE_INFO_REPORT_ID};
+
+// Where reports come from, and output reports go to
+// Everything after the transport sees reports in the HID report format
+pub trait Transport {
+    fn device_info(&self) -> DeviceInfo;
+    // Whether encoders send step counts, from the firmware info
+    // None if it isn't known, the format is then told apart by the report length
+    fn step_counts(&self) -> Option<bool> {
+        self.device_info()
+            .firmware
+            .and_then(|firmware| firmware.layout.step_counts)
+    }
+    // Returns Ok(0) when no report is waiting rather than blocking
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    fn write(&mut self, report: &[u8]) -> Result<()>;
+}
+
+pub struct HidTransport {
+    device: hidapi::HidDevice,
+    device_info: DeviceInfo,
+}
+
+impl HidTransport {
+    pub fn new(device: hidapi::HidDevice, hid_device_info: &hidapi::DeviceInfo) -> Result<Self> {
+        device.set_blocking_mode(false)?;
+
+        let mut report = [0u8; 64];
+        report[0] = FIRMWARE_INFO_REPORT_ID;
+        let firmware = match device.get_feature_report(&mut report) {
+            Ok(n_bytes) => parse_firmware_info(&report[..n_bytes]),
+            Err(e) => Err(e.into()),
+        };
+        let firmware = match firmware {
+            Ok(firmware) => Some(firmware),
+            Err(e) => {
+                eprintln!("Failed to query firmware info: {}", e);
+                None
+            }
+        };
+
+        let device_info = DeviceInfo::new(
+            hid_device_info.manufacturer_string().map(String::from),
+            hid_device_info.product_string().map(String::from),
+            hid_device_info.serial_number().map(String::from),
+            firmware,
+        );
+
+        Ok(HidTransport {
+            device,
+            device_info,
+        })
+    }
+}
+
+impl Transport for HidTransport {
+    fn device_info(&self) -> DeviceInfo {
+        self.device_info.clone()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.device.read(buf)?)
+    }
+
+    fn write(&mut self, report: &[u8]) -> Result<()> {
+        self.device.write(report)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::device_info::{DeviceInfo, FirmwareInfo, Layout, MIN_FIRMWARE_VERSION};
+use crate::trace::parse_report;
+use crate::transport::Transport;
+
+// Set to "tcp:<address>" or "script:<path>" to read reports from a virtual device instead of the macropad
+pub const VIRTUAL_DEVICE_ENV: &str = "MACROPAD_DEVICE";
+
+// How long a write to a socket may wait for the other end to catch up
+const SOCKET_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptStep {
+    Report(Vec<u8>),
+    Wait(Duration),
+}
+
+// Scripts have a report per line as hex bytes, e.g. "10 00", or "wait <ms>" to pause
+// Blank lines and lines starting with "#" are ignored
+pub fn parse_script(script: &str) -> Result<Vec<ScriptStep>> {
+    script
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            Some(parse_script_line(line).map_err(|e| anyhow!("Line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+fn parse_script_line(line: &str) -> Result<ScriptStep> {
+    match line.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["wait", ms] => Ok(ScriptStep::Wait(Duration::from_millis(ms.parse()?))),
+        ref bytes => Ok(ScriptStep::Report(parse_report(bytes.iter().copied())?)),
+    }
+}
+
+// Virtual devices behave as a 12 key pad with one encoder on the oldest supported firmware
+// Either report format can be simulated
+fn virtual_device_info(device: &str) -> DeviceInfo {
+    DeviceInfo::new(
+        None,
+        Some(format!("Virtual device ({})", device)),
+        None,
+        Some(FirmwareInfo {
+            version: MIN_FIRMWARE_VERSION,
+            layout: Layout {
+                buttons: 12,
+                encoders: 1,
+                columns: 3,
+                rgb_leds: true,
+                display: true,
+                step_counts: None,
+            },
+        }),
+    )
+}
+
+pub fn listen_virtual(handle: &tauri::AppHandle, device: &str) {
+    match device.split_once(':') {
+        Some(("tcp", address)) => listen_socket(handle, address),
+        Some(("script", path)) => run_script(handle, path),
+        _ => eprintln!("Unsupported virtual device: {}", device),
+    }
+}
+
+// Reports over a socket start with a byte giving their length, e.g. "02 10 00"
+// so longer reports such as step counts can be sent as well
+pub fn frame_report(report: &[u8]) -> Result<Vec<u8>> {
+    let length = u8::try_from(report.len())
+        .map_err(|_| anyhow!("Report of {} bytes is too long to send", report.len()))?;
+    let mut frame = vec![length];
+    frame.extend_from_slice(report);
+    Ok(frame)
+}
+
+// Removes the first complete report from the received bytes
+pub fn take_report(pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let length = *pending.first()? as usize;
+    if pending.len() < 1 + length {
+        return None;
+    }
+    let report = pending[1..1 + length].to_vec();
+    pending.drain(..1 + length);
+    Some(report)
+}
+
+// Output reports are written back to the connection
+pub struct SocketTransport {
+    stream: TcpStream,
+    address: String,
+    pending: Vec<u8>,
+}
+
+impl SocketTransport {
+    pub fn new(stream: TcpStream, address: &str) -> Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(SocketTransport {
+            stream,
+            address: address.to_string(),
+            pending: vec![],
+        })
+    }
+}
+
+impl Transport for SocketTransport {
+    fn device_info(&self) -> DeviceInfo {
+        virtual_device_info(&format!("tcp:{}", self.address))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut chunk = [0u8; 64];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => bail!("Connection closed"),
+            Ok(n_bytes) => self.pending.extend_from_slice(&chunk[..n_bytes]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        match take_report(&mut self.pending) {
+            Some(report) => {
+                let n_bytes = report.len().min(buf.len());
+                buf[..n_bytes].copy_from_slice(&report[..n_bytes]);
+                Ok(n_bytes)
+            }
+            None => Ok(0),
+        }
+    }
+
+    // The socket is nonblocking, so a full send buffer is waited out rather than treated as an error
+    fn write(&mut self, report: &[u8]) -> Result<()> {
+        let frame = frame_report(report)?;
+        let started_at = Instant::now();
+        let mut written = 0;
+        while written < frame.len() {
+            match self.stream.write(&frame[written..]) {
+                Ok(0) => bail!("Connection closed"),
+                Ok(n_bytes) => written += n_bytes,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if started_at.elapsed() > SOCKET_WRITE_TIMEOUT {
+                        bail!("Timed out writing report");
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Each connection is treated as the device being plugged in, one at a time
+fn listen_socket(handle: &tauri::AppHandle, address: &str) {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen for virtual device on {}: {}", address, e);
+            return;
+        }
+    };
+    println!("Listening for virtual device on {}", address);
+
+    for stream in listener.incoming() {
+        let mut transport = match stream
+            .map_err(anyhow::Error::from)
+            .and_then(|stream| SocketTransport::new(stream, address))
+        {
+            Ok(transport) => transport,
+            Err(e) => {
+                eprintln!("Failed to accept virtual device: {}", e);
+                continue;
+            }
+        };
+
+        let e = crate::run_device(handle, &mut transport);
+        println!("Virtual device disconnected: {}", e);
+    }
+}
+
+// Plays the script through once, as if the device was unplugged at the end
+// Output reports are printed
+pub struct ScriptTransport {
+    path: String,
+    steps: std::vec::IntoIter<ScriptStep>,
+    resume_at: Instant,
+}
+
+impl ScriptTransport {
+    pub fn new(path: &str) -> Result<Self> {
+        let steps = parse_script(&std::fs::read_to_string(path)?)?;
+        Ok(ScriptTransport {
+            path: path.to_string(),
+            steps: steps.into_iter(),
+            resume_at: Instant::now(),
+        })
+    }
+}
+
+impl Transport for ScriptTransport {
+    fn device_info(&self) -> DeviceInfo {
+        virtual_device_info(&format!("script:{}", self.path))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if Instant::now() < self.resume_at {
+            return Ok(0);
+        }
+
+        match self.steps.next() {
+            Some(ScriptStep::Wait(duration)) => {
+                self.resume_at = Instant::now() + duration;
+                Ok(0)
+            }
+            Some(ScriptStep::Report(report)) => {
+                let n_bytes = report.len().min(buf.len());
+                buf[..n_bytes].copy_from_slice(&report[..n_bytes]);
+                Ok(n_bytes)
+            }
+            None => bail!("End of script"),
+        }
+    }
+
+    fn write(&mut self, report: &[u8]) -> Result<()> {
+        println!("Output report: {:?}", report);
+        Ok(())
+    }
+}
+
+fn run_script(handle: &tauri::AppHandle, path: &str) {
+    let mut transport = match ScriptTransport::new(path) {
+        Ok(transport) => transport,
+        Err(e) => {
+            eprintln!("Failed to load virtual device script {}: {}", path, e);
+            return;
+        }
+    };
+
+    let e = crate::run_device(handle, &mut transport);
+    println!("Virtual device finished: {}", e);
+}
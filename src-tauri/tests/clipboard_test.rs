@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod clipboard_test {
+    use macropad_console_lib::clipboard::{apply_transform, TextTransform};
+    use macropad_console_lib::config::Operation;
+
+    #[test]
+    fn test_apply_transform() {
+        assert_eq!(
+            apply_transform("Hello, wörld", &TextTransform::Uppercase).unwrap(),
+            "HELLO, WÖRLD"
+        );
+        assert_eq!(
+            apply_transform("Hello", &TextTransform::Lowercase).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            apply_transform("  padded \n", &TextTransform::Trim).unwrap(),
+            "padded"
+        );
+        assert_eq!(
+            apply_transform(
+                "2024-05-01 and 2024-06-02",
+                &TextTransform::RegexReplace {
+                    pattern: r"(\d+)-(\d+)-(\d+)".to_string(),
+                    replacement: "$3/$2/$1".to_string(),
+                }
+            )
+            .unwrap(),
+            "01/05/2024 and 02/06/2024"
+        );
+    }
+
+    #[test]
+    fn test_transform_operations() {
+        let operations = serde_json::from_str::<Vec<Operation>>(
+            r#"[
+                { "clipboardSave": {} },
+                { "clipboardTransform": { "kind": "trim" } },
+                { "clipboardTransform": { "kind": { "regexReplace": { "pattern": "(", "replacement": "" } } } },
+                { "clipboardRestore": {} }
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(operations[0].validate().is_ok());
+        assert!(operations[1].validate().is_ok());
+        // Invalid patterns are caught when the config is loaded
+        assert!(operations[2].validate().is_err());
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod condition_test {
+    use std::collections::{HashMap, HashSet};
+
+    use chrono::NaiveTime;
+    use macropad_console_lib::condition::{Condition, ConditionContext};
+    use macropad_console_lib::config::{AppConfig, Modifier};
+    use macropad_console_lib::toggle::ToggleStates;
+
+    fn context(time: &str) -> ConditionContext {
+        let mut toggles = ToggleStates::default();
+        toggles.flip("mute");
+        ConditionContext {
+            window_title: "Untitled - Photoshop".to_string(),
+            app_name: "Photoshop.exe".to_string(),
+            held: HashSet::from_iter(vec![Modifier::Button(4)]),
+            toggles,
+            variables: HashMap::from_iter(vec![("mode".to_string(), "paint".to_string())]),
+            now: NaiveTime::parse_from_str(time, "%H:%M").unwrap(),
+        }
+    }
+
+    fn condition(json: &str) -> Condition {
+        serde_json::from_str::<Condition>(json).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let context = context("12:00");
+
+        assert!(condition(r#"{ "windowTitle": { "pattern": "Photoshop$" } }"#).evaluate(&context));
+        assert!(!condition(r#"{ "appName": { "pattern": "^blender" } }"#).evaluate(&context));
+        assert!(condition(r#"{ "held": { "input": "BTN_4" } }"#).evaluate(&context));
+        assert!(!condition(r#"{ "held": { "input": "ENC_0_PUSH" } }"#).evaluate(&context));
+        assert!(condition(r#"{ "toggleOn": { "id": "mute" } }"#).evaluate(&context));
+        assert!(!condition(r#"{ "toggleOn": { "id": "record" } }"#).evaluate(&context));
+        assert!(
+            condition(r#"{ "variable": { "name": "mode", "value": "paint" } }"#).evaluate(&context)
+        );
+        assert!(
+            !condition(r#"{ "variable": { "name": "unset", "value": "" } }"#).evaluate(&context)
+        );
+
+        let combined = condition(
+            r#"{ "all": [
+                { "toggleOn": { "id": "mute" } },
+                { "not": { "any": [
+                    { "held": { "input": "BTN_5" } },
+                    { "appName": { "pattern": "blender" } }
+                ] } }
+            ] }"#,
+        );
+        assert!(combined.evaluate(&context));
+        assert!(condition(r#"{ "all": [] }"#).evaluate(&context));
+        assert!(!condition(r#"{ "any": [] }"#).evaluate(&context));
+    }
+
+    #[test]
+    fn test_time_between() {
+        let working_hours = condition(r#"{ "timeBetween": { "start": "09:00", "end": "17:30" } }"#);
+        assert!(working_hours.evaluate(&context("09:00")));
+        assert!(!working_hours.evaluate(&context("17:30")));
+        assert!(!working_hours.evaluate(&context("08:59")));
+
+        // Wraps past midnight
+        let night = condition(r#"{ "timeBetween": { "start": "22:00", "end": "06:00" } }"#);
+        assert!(night.evaluate(&context("23:15")));
+        assert!(night.evaluate(&context("01:00")));
+        assert!(!night.evaluate(&context("12:00")));
+    }
+
+    #[test]
+    fn test_validate_if() {
+        let config = |condition: &str| {
+            serde_json::from_str::<AppConfig>(&format!(
+                r#"{{
+                "applicationProfiles": {{}},
+                "macros": {{
+                    "test": [{{ "if": {{
+                        "condition": {},
+                        "then": [{{ "keyTap": {{ "key": "F5" }} }}],
+                        "else": [{{ "callMacro": {{ "name": "missing" }} }}]
+                    }} }}]
+                }}
+            }}"#,
+                condition
+            ))
+            .unwrap()
+        };
+
+        let e = config(r#"{ "toggleOn": { "id": "mute" } }"#)
+            .validate()
+            .unwrap_err();
+        assert!(e.to_string().contains("Unknown macro \"missing\""));
+        let e = config(r#"{ "not": { "timeBetween": { "start": "9am", "end": "17:00" } } }"#)
+            .validate()
+            .unwrap_err();
+        assert!(e.to_string().contains("Invalid time \"9am\""));
+        let e = config(r#"{ "held": { "input": "BTN_X" } }"#)
+            .validate()
+            .unwrap_err();
+        assert!(e.to_string().contains("Unknown input \"BTN_X\""));
+    }
+}
@@ -5,15 +5,24 @@ mod config_test {
     use paste::paste;
     use serde_test::{assert_tokens, Token};
 
-    use macropad_console_lib::config::{Action, AppConfig, ApplicationProfile, KeyCombination};
+    use enigo::Key;
+    use macropad_console_lib::condition::Condition;
+    use macropad_console_lib::config::{
+        held_keys, parse_shortcut, Action, AppConfig, ApplicationProfile, Command, KeyCombination,
+        LedState, Modifier, MouseButton, Operation, ScrollAxis,
+    };
 
     #[test]
     fn test_serialize_and_deserialize_config() {
         let config = AppConfig {
             application_profiles: HashMap::from_iter(vec![(
                 "test_profile".to_string(),
-                ApplicationProfile { bindings: vec![] },
+                ApplicationProfile {
+                    bindings: vec![],
+                    ..Default::default()
+                },
             )]),
+            ..Default::default()
         };
 
         dbg!(&config);
@@ -42,7 +51,7 @@ mod config_test {
     fn test_ser_de_key_combination() {
         let expected = "BTN_4+ENC_0_INC";
         let c = KeyCombination {
-            modifiers: Some(HashSet::from_iter(vec![4])),
+            modifiers: Some(HashSet::from_iter(vec![Modifier::Button(4)])),
             action: Action::EncoderIncrement { id: 0 },
         };
 
@@ -98,20 +107,53 @@ mod config_test {
         two_modifiers_btn_press,
         &[Token::Str("BTN_4+BTN_8+BTN_7"),],
         KeyCombination {
-            modifiers: Some(HashSet::from_iter(vec![4, 8])),
+            modifiers: Some(HashSet::from_iter(vec![Modifier::Button(4), Modifier::Button(8)])),
             action: Action::ButtonPress { id: 7 },
         }
     );
+    ser_de_key_combination_test!(
+        encoder_push,
+        &[Token::Str("ENC_0_PUSH"),],
+        KeyCombination {
+            modifiers: None,
+            action: Action::EncoderPush { id: 0 },
+        }
+    );
+    ser_de_key_combination_test!(
+        encoder_push_modifier,
+        &[Token::Str("ENC_0_PUSH+ENC_0_INC"),],
+        KeyCombination {
+            modifiers: Some(HashSet::from_iter(vec![Modifier::EncoderPush(0)])),
+            action: Action::EncoderIncrement { id: 0 },
+        }
+    );
+    ser_de_key_combination_test!(
+        encoder_rotation_modifier,
+        &[Token::Str("BTN_2+ENC_0_DEC+BTN_1"),],
+        KeyCombination {
+            modifiers: Some(HashSet::from_iter(vec![
+                Modifier::Button(2),
+                Modifier::EncoderDecrement(0)
+            ])),
+            action: Action::ButtonPress { id: 1 },
+        }
+    );
+
+    #[test]
+    fn test_key_combination_invalid_id() {
+        assert!(serde_json::from_str::<KeyCombination>(r#""BTN_X""#).is_err());
+        assert!(serde_json::from_str::<KeyCombination>(r#""ENC_X_PUSH+BTN_1""#).is_err());
+    }
 
     #[test]
     fn test_key_combination_eq() {
         let l = KeyCombination {
-            modifiers: Some(HashSet::from_iter(vec![4, 8])),
+            modifiers: Some(HashSet::from_iter(vec![Modifier::Button(4), Modifier::Button(8)])),
             action: Action::ButtonPress { id: 7 },
         };
 
         let json = r#""BTN_4+BTN_8+BTN_7""#;
-        let r = serde_json::from_str::<KeyCombination>(&json).unwrap();
+        let r = serde_json::from_str::<KeyCombination>(json).unwrap();
 
         assert_eq!(l, r);
     }
@@ -159,9 +201,436 @@ mod config_test {
     key_combination_eq_test!(
         two_modifiers_unordered_btn_press,
         KeyCombination {
-            modifiers: Some(HashSet::from_iter(vec![4, 8])),
+            modifiers: Some(HashSet::from_iter(vec![Modifier::Button(4), Modifier::Button(8)])),
             action: Action::ButtonPress { id: 7 },
         },
         r#""BTN_8+BTN_4+BTN_7""#
     );
+
+    #[test]
+    fn test_command_acceleration() {
+        let command = serde_json::from_str::<Command>(
+            r#"{
+                "displayName": "Scroll",
+                "operations": [],
+                "acceleration": [
+                    { "minSpeed": 30, "multiplier": 10 },
+                    { "minSpeed": 10, "multiplier": 4 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(command.get_repetitions(1, None), 1);
+        assert_eq!(command.get_repetitions(1, Some(5.0)), 1);
+        assert_eq!(command.get_repetitions(1, Some(10.0)), 4);
+        assert_eq!(command.get_repetitions(2, Some(50.0)), 20);
+    }
+
+    #[test]
+    fn test_command_without_acceleration() {
+        let command = Command::default();
+
+        assert_eq!(command.get_repetitions(3, Some(50.0)), 3);
+    }
+
+    #[test]
+    fn test_profile_led_layers() {
+        let profile = serde_json::from_str::<ApplicationProfile>(
+            r#"{
+                "bindings": [],
+                "leds": { "0": "on", "1": "on" },
+                "layers": {
+                    "BTN_4": { "1": "off", "2": { "rgb": { "r": 255, "g": 0, "b": 0 } } },
+                    "BTN_4+ENC_0_PUSH": { "2": "on" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let leds = profile.get_leds(&HashSet::new());
+        assert_eq!(
+            leds,
+            HashMap::from_iter(vec![(0, LedState::On), (1, LedState::On)])
+        );
+
+        let leds = profile.get_leds(&HashSet::from_iter(vec![Modifier::Button(4)]));
+        assert_eq!(
+            leds,
+            HashMap::from_iter(vec![
+                (0, LedState::On),
+                (1, LedState::Off),
+                (2, LedState::Rgb { r: 255, g: 0, b: 0 }),
+            ])
+        );
+
+        let leds = profile.get_leds(&HashSet::from_iter(vec![
+            Modifier::Button(4),
+            Modifier::EncoderPush(0),
+        ]));
+        assert_eq!(leds.get(&2), Some(&LedState::On));
+    }
+
+    #[test]
+    fn test_profile_toggle_leds() {
+        let profile = serde_json::from_str::<ApplicationProfile>(
+            r#"{
+                "bindings": [
+                    ["BTN_0", {
+                        "displayName": "Mute",
+                        "toggle": {
+                            "id": "mute",
+                            "on": [{ "keyTap": { "key": "MUTE" } }],
+                            "off": [{ "keyTap": { "key": "MUTE" } }],
+                            "led": { "button": 0, "on": { "rgb": { "r": 255, "g": 0, "b": 0 } } }
+                        }
+                    }],
+                    ["BTN_1", {
+                        "displayName": "Record",
+                        "toggle": { "id": "record", "on": [], "off": [] }
+                    }]
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let leds = profile.get_toggle_leds(&HashMap::new());
+        assert_eq!(leds, HashMap::from_iter(vec![(0, LedState::Off)]));
+
+        let leds = profile.get_toggle_leds(&HashMap::from_iter(vec![
+            ("mute".to_string(), true),
+            ("record".to_string(), true),
+        ]));
+        assert_eq!(
+            leds,
+            HashMap::from_iter(vec![(0, LedState::Rgb { r: 255, g: 0, b: 0 })])
+        );
+    }
+
+    #[test]
+    fn test_validate_midi_devices() {
+        let config = |channel: u8, id: u8, encoder: u8| {
+            serde_json::from_str::<AppConfig>(&format!(
+                r#"{{
+                "applicationProfiles": {{}},
+                "midiDevices": [{{
+                    "port": "nanoKONTROL",
+                    "channel": {},
+                    "notes": {{ "36": {} }},
+                    "controllers": {{ "16": {{ "encoder": {} }} }}
+                }}]
+            }}"#,
+                channel, id, encoder
+            ))
+            .unwrap()
+        };
+
+        assert!(config(1, 11, 0).validate().is_ok());
+        assert!(config(16, 0, 0).validate().is_ok());
+        let e = config(0, 0, 0).validate().unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("MIDI device \"nanoKONTROL\": channel 0"));
+        assert!(config(17, 0, 0).validate().is_err());
+
+        // Notes can only stand in for the pad's buttons, and there is one encoder
+        let e = config(1, 12, 0).validate().unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("MIDI device \"nanoKONTROL\": note 36 has button id 12"));
+        let e = config(1, 0, 1).validate().unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("MIDI device \"nanoKONTROL\": controller 16 has encoder 1"));
+    }
+
+    #[test]
+    fn test_validate_keyboards() {
+        let config = |id: u8| {
+            serde_json::from_str::<AppConfig>(&format!(
+                r#"{{
+                "applicationProfiles": {{}},
+                "keyboards": [{{ "device": "Numpad", "keys": {{ "KEY_KP1": {} }} }}]
+            }}"#,
+                id
+            ))
+            .unwrap()
+        };
+
+        assert!(config(12).validate().is_ok());
+        assert!(config(31).validate().is_ok());
+        let e = config(32).validate().unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("Keyboard \"Numpad\": key KEY_KP1 has button id 32"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_validate_keyboard_key_names() {
+        let mut config = serde_json::from_str::<AppConfig>(
+            r#"{
+            "applicationProfiles": {},
+            "keyboards": [{ "device": "Numpad", "keys": { "KEY_KP1": 1, "KEY_NOPE": 2 } }]
+        }"#,
+        )
+        .unwrap();
+
+        let errors = config.remove_invalid();
+        assert_eq!(errors, vec!["Keyboard \"Numpad\": unknown key KEY_NOPE"]);
+        assert_eq!(
+            config.keyboards[0].keys,
+            HashMap::from([("KEY_KP1".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_validate_key_names() {
+        let config = |key: &str| {
+            serde_json::from_str::<AppConfig>(&format!(
+                r#"{{
+                "applicationProfiles": {{
+                    "test_profile": {{
+                        "bindings": [
+                            ["BTN_0", {{
+                                "displayName": "Test",
+                                "operations": [
+                                    {{ "keyTap": {{ "key": "F5" }} }},
+                                    {{ "repeat": {{ "times": 2, "operations": [{{ "keyTap": {{ "key": "{}" }} }}] }} }}
+                                ]
+                            }}]
+                        ]
+                    }}
+                }}
+            }}"#,
+                key
+            ))
+            .unwrap()
+        };
+
+        assert!(config("PAGEDOWN").validate().is_ok());
+        assert!(config("a").validate().is_ok());
+        let e = config("PAGEDOWNN").validate().unwrap_err();
+        assert!(e.to_string().contains("Unknown key \"PAGEDOWNN\""));
+    }
+
+    #[test]
+    fn test_parse_shortcut() {
+        assert_eq!(
+            parse_shortcut("Ctrl+Shift+T").unwrap(),
+            vec![Key::Control, Key::Shift, Key::Unicode('t')]
+        );
+        assert_eq!(parse_shortcut("alt + F4").unwrap(), vec![Key::Alt, Key::F4]);
+        assert_eq!(parse_shortcut("F5").unwrap(), vec![Key::F5]);
+        assert_eq!(
+            parse_shortcut("Ctrl++").unwrap(),
+            vec![Key::Control, Key::Unicode('+')]
+        );
+
+        assert!(parse_shortcut("Ctrl+").is_err());
+        assert!(parse_shortcut("Ctrl+Shft+T").is_err());
+        assert!(parse_shortcut("").is_err());
+    }
+
+    #[test]
+    fn test_mouse_operations() {
+        let operations = serde_json::from_str::<Vec<Operation>>(
+            r#"[
+                { "mouseClick": {} },
+                { "mouseClick": { "button": "right" } },
+                { "mouseMove": { "x": 10, "y": -5, "relative": true } },
+                { "mouseDrag": { "x": 100, "y": 200 } },
+                { "scroll": { "axis": "horizontal", "amount": -3 } },
+                { "scroll": { "amount": 1 } }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            operations,
+            vec![
+                Operation::MouseClick {
+                    button: MouseButton::Left
+                },
+                Operation::MouseClick {
+                    button: MouseButton::Right
+                },
+                Operation::MouseMove {
+                    x: 10,
+                    y: -5,
+                    relative: true
+                },
+                Operation::MouseDrag {
+                    button: MouseButton::Left,
+                    x: 100,
+                    y: 200,
+                    relative: false
+                },
+                Operation::Scroll {
+                    axis: ScrollAxis::Horizontal,
+                    amount: -3
+                },
+                Operation::Scroll {
+                    axis: ScrollAxis::Vertical,
+                    amount: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_macro_names() {
+        let config = |name: &str| {
+            serde_json::from_str::<AppConfig>(&format!(
+                r#"{{
+                "applicationProfiles": {{
+                    "test_profile": {{
+                        "bindings": [
+                            ["BTN_0", {{
+                                "displayName": "Test",
+                                "operations": [{{ "callMacro": {{ "name": "{}" }} }}]
+                            }}]
+                        ]
+                    }}
+                }},
+                "macros": {{
+                    "copy": [{{ "shortcut": {{ "keys": "Ctrl+C" }} }}],
+                    "copy twice": [
+                        {{ "repeat": {{ "times": 2, "operations": [{{ "callMacro": {{ "name": "copy" }} }}] }} }}
+                    ]
+                }}
+            }}"#,
+                name
+            ))
+            .unwrap()
+        };
+
+        assert!(config("copy").validate().is_ok());
+        assert!(config("copy twice").validate().is_ok());
+        let e = config("paste").validate().unwrap_err();
+        assert!(e.to_string().contains("Unknown macro \"paste\""));
+
+        // Macros are checked too
+        let mut config = config("copy");
+        config.macros.insert(
+            "broken".to_string(),
+            vec![Operation::CallMacro {
+                name: "missing".to_string(),
+            }],
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_remove_invalid() {
+        let mut config = serde_json::from_str::<AppConfig>(
+            r#"{
+            "applicationProfiles": {
+                "test_profile": {
+                    "bindings": [
+                        ["BTN_0", { "displayName": "Good", "operations": [{ "keyTap": { "key": "F5" } }] }],
+                        ["BTN_1", { "displayName": "Bad key", "operations": [{ "keyTap": { "key": "F55" } }] }],
+                        ["BTN_2", { "displayName": "Bad macro", "operations": [{ "callMacro": { "name": "outer" } }] }]
+                    ]
+                }
+            },
+            "keyboards": [{ "device": "Numpad", "keys": { "KEY_KP1": 1, "KEY_KP2": 40 } }],
+            "midiDevices": [{ "port": "nanoKONTROL", "channel": 17 }],
+            "macros": {
+                "inner": [{ "callMacro": { "name": "missing" } }],
+                "outer": [{ "callMacro": { "name": "inner" } }]
+            }
+        }"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        // Only the invalid parts are dropped, including what relied on them
+        let errors = config.remove_invalid();
+        assert_eq!(errors.len(), 6);
+        let bindings = &config.application_profiles["test_profile"].bindings;
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].1.display_name, "Good");
+        assert_eq!(
+            config.keyboards[0].keys,
+            HashMap::from([("KEY_KP1".to_string(), 1)])
+        );
+        assert!(config.midi_devices.is_empty());
+        assert!(config.macros.is_empty());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_held_keys() {
+        let key_press = |key: &str| Operation::KeyPress {
+            key: key.to_string(),
+        };
+        let key_release = |key: &str| Operation::KeyRelease {
+            key: key.to_string(),
+        };
+
+        let operations = vec![
+            key_press("LSHIFT"),
+            key_press("LCONTROL"),
+            key_release("LCONTROL"),
+            key_press("A"),
+            Operation::Shortcut {
+                keys: "Ctrl+C".to_string(),
+            },
+        ];
+        assert_eq!(held_keys(&operations, &HashMap::new()), vec!["A", "LSHIFT"]);
+
+        // Keys pressed inside nested operations and called macros are held too
+        let macros = HashMap::from([
+            ("hold shift".to_string(), vec![key_press("LSHIFT")]),
+            (
+                "loop".to_string(),
+                vec![Operation::CallMacro {
+                    name: "loop".to_string(),
+                }],
+            ),
+        ]);
+        let operations = vec![
+            Operation::CallMacro {
+                name: "hold shift".to_string(),
+            },
+            Operation::CallMacro {
+                name: "loop".to_string(),
+            },
+            Operation::Repeat {
+                times: 2,
+                operations: vec![key_press("LCONTROL")],
+            },
+            Operation::If {
+                condition: Condition::ToggleOn {
+                    id: "mute".to_string(),
+                },
+                then: vec![key_press("LMENU"), key_release("LCONTROL")],
+                otherwise: vec![],
+            },
+        ];
+        assert_eq!(
+            held_keys(&operations, &macros),
+            vec!["LMENU", "LCONTROL", "LSHIFT"]
+        );
+
+        // Only toggles leaving keys held are reported
+        let config = serde_json::from_str::<AppConfig>(
+            r#"{
+            "applicationProfiles": {
+                "test_profile": {
+                    "bindings": [
+                        ["BTN_0", { "displayName": "Hold", "toggle": { "id": "hold", "on": [{ "keyPress": { "key": "LSHIFT" } }], "off": [{ "keyRelease": { "key": "LSHIFT" } }] } }],
+                        ["BTN_1", { "displayName": "Mute", "toggle": { "id": "mute", "on": [{ "keyTap": { "key": "F5" } }], "off": [] } }]
+                    ]
+                }
+            }
+        }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.get_toggles_holding_keys(),
+            HashSet::from(["hold".to_string()])
+        );
+    }
 }
@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod debounce_test {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use macropad_console_lib::config::{Action, DebounceConfig};
+    use macropad_console_lib::debounce::ReportFilter;
+    use macropad_console_lib::hid::handle_report;
+    use macropad_console_lib::macropad_state::MacropadState;
+
+    // Feeds (milliseconds since start, report) pairs through the filter, polling every millisecond in between
+    // Returns the actions handle_report emits for whatever the filter passes on
+    fn run(config: DebounceConfig, reports: &[(u64, Vec<u8>)]) -> Vec<Action> {
+        let start = Instant::now();
+        let mut filter = ReportFilter::new(config, None);
+        let mut state = MacropadState::default();
+        let mut actions = vec![];
+
+        let end = reports.last().map(|(t, _)| t + 100).unwrap_or(0);
+        let mut reports = reports.iter().peekable();
+        for t in 0..=end {
+            let now = start + Duration::from_millis(t);
+
+            let mut passed_on = vec![];
+            while let Some((_, report)) = reports.next_if(|(at, _)| *at == t) {
+                passed_on.extend(filter.filter(report, now));
+            }
+            passed_on.extend(filter.poll(now));
+
+            for report in passed_on {
+                let (new_state, action, _) = handle_report(state, &report);
+                state = new_state;
+                if action != Action::None {
+                    actions.push(action);
+                }
+            }
+        }
+
+        actions
+    }
+
+    fn button_config(button_ms: u64) -> DebounceConfig {
+        DebounceConfig {
+            button_ms,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disabled_passes_chatter_through() {
+        let actions = run(
+            DebounceConfig::default(),
+            &[(0, vec![1, 0]), (1, vec![0, 0]), (2, vec![1, 0]), (50, vec![0, 0])],
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::ButtonPress { id: 0 },
+                Action::ButtonRelease { id: 0 },
+                Action::ButtonPress { id: 0 },
+                Action::ButtonRelease { id: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_press_chatter_is_dropped() {
+        let actions = run(
+            button_config(10),
+            &[(0, vec![1, 0]), (1, vec![0, 0]), (2, vec![1, 0]), (50, vec![0, 0])],
+        );
+
+        assert_eq!(
+            actions,
+            vec![Action::ButtonPress { id: 0 }, Action::ButtonRelease { id: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_release_chatter_is_dropped() {
+        let actions = run(
+            button_config(10),
+            &[(0, vec![1, 0]), (50, vec![0, 0]), (51, vec![1, 0]), (52, vec![0, 0])],
+        );
+
+        assert_eq!(
+            actions,
+            vec![Action::ButtonPress { id: 0 }, Action::ButtonRelease { id: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_quick_release_is_passed_on_once_settled() {
+        // The release arrives inside the window and no further report follows
+        let actions = run(button_config(10), &[(0, vec![1, 0]), (3, vec![0, 0])]);
+
+        assert_eq!(
+            actions,
+            vec![Action::ButtonPress { id: 0 }, Action::ButtonRelease { id: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_per_button_window() {
+        let config = DebounceConfig {
+            button_ms: 0,
+            buttons: HashMap::from_iter(vec![(1, 20)]),
+            ..Default::default()
+        };
+        let actions = run(
+            config,
+            &[
+                (0, vec![0b11, 0]),
+                (5, vec![0b00, 0]),
+                (6, vec![0b11, 0]),
+                (50, vec![0b00, 0]),
+            ],
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::ButtonPress { id: 1 },
+                Action::ButtonRelease { id: 0 },
+                Action::ButtonPress { id: 0 },
+                Action::ButtonRelease { id: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoder_reversal_glitch_is_dropped() {
+        let config = DebounceConfig {
+            encoder_reversal_ms: 30,
+            ..Default::default()
+        };
+        let actions = run(
+            config,
+            &[
+                (0, vec![0, 0, 1]),
+                (10, vec![0, 0, 1]),
+                (15, vec![0, 0, (-1i8) as u8]),
+                (20, vec![0, 0, 1]),
+                (100, vec![0, 0, (-1i8) as u8]),
+            ],
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::EncoderIncrement { id: 0 },
+                Action::EncoderIncrement { id: 0 },
+                Action::EncoderIncrement { id: 0 },
+                Action::EncoderDecrement { id: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_two_bit_encoder_reversal_glitch_is_dropped() {
+        let config = DebounceConfig {
+            encoder_reversal_ms: 30,
+            ..Default::default()
+        };
+        let actions = run(
+            config,
+            &[
+                (0, vec![0, 0b0001_0000]),
+                (5, vec![0, 0]),
+                (10, vec![0, 0b0011_0000]),
+                (12, vec![0, 0]),
+                (15, vec![0, 0b0001_0000]),
+                (18, vec![0, 0]),
+            ],
+        );
+
+        assert_eq!(
+            actions,
+            vec![
+                Action::EncoderIncrement { id: 0 },
+                Action::EncoderIncrement { id: 0 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod device_info_test {
+    use macropad_console_lib::device_info::{
+        parse_firmware_info, DeviceInfo, FirmwareVersion, FIRMWARE_INFO_REPORT_ID,
+    };
+
+    #[test]
+    fn test_parse_firmware_info() {
+        let firmware =
+            parse_firmware_info(&[FIRMWARE_INFO_REPORT_ID, 1, 2, 3, 12, 1, 3, 0b101]).unwrap();
+
+        assert_eq!(
+            firmware.version,
+            FirmwareVersion {
+                major: 1,
+                minor: 2,
+                patch: 3
+            }
+        );
+        assert_eq!(firmware.layout.buttons, 12);
+        assert_eq!(firmware.layout.encoders, 1);
+        assert_eq!(firmware.layout.columns, 3);
+        assert!(firmware.layout.rgb_leds);
+        assert!(!firmware.layout.display);
+        assert_eq!(firmware.layout.step_counts, Some(true));
+
+        assert!(parse_firmware_info(&[FIRMWARE_INFO_REPORT_ID, 1, 2]).is_err());
+        assert!(parse_firmware_info(&[0x01, 1, 2, 3, 12, 1, 3, 0]).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_firmware() {
+        let device_info =
+            |report: &[u8]| DeviceInfo::new(None, None, None, parse_firmware_info(report).ok());
+
+        assert!(device_info(&[FIRMWARE_INFO_REPORT_ID, 1, 0, 0, 12, 1, 3, 0]).supported);
+        // Too old
+        assert!(!device_info(&[FIRMWARE_INFO_REPORT_ID, 0, 9, 0, 12, 1, 3, 0]).supported);
+        // More buttons than the app tracks
+        assert!(!device_info(&[FIRMWARE_INFO_REPORT_ID, 1, 0, 0, 16, 1, 4, 0]).supported);
+        // Unknown firmware
+        assert!(!device_info(&[]).supported);
+    }
+}
@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod dfu_test {
+    use anyhow::Result;
+
+    use macropad_console_lib::dfu::{
+        dfu_crc, download, parse_transfer_size, verify_image, DfuTransport, DFU_CLRSTATUS,
+        DFU_DNLOAD, DFU_GETSTATUS, DFU_STATE_DNLOAD_IDLE, DFU_STATE_ERROR, DFU_STATE_IDLE,
+        DFU_STATUS_OK,
+    };
+
+    const DFU_STATE_DNBUSY: u8 = 4;
+    const DFU_STATE_MANIFEST: u8 = 7;
+    const DFU_STATUS_ERR_WRITE: u8 = 3;
+
+    // Bootloader which is busy for one poll after each request
+    struct MockBootloader {
+        state: u8,
+        status: u8,
+        busy: bool,
+        blocks: Vec<(u16, Vec<u8>)>,
+        fail_block: Option<u16>,
+        // Returned instead of the status once the firmware is being written
+        manifest_error: Option<rusb::Error>,
+    }
+
+    impl MockBootloader {
+        fn new(state: u8) -> Self {
+            MockBootloader {
+                state,
+                status: DFU_STATUS_OK,
+                busy: false,
+                blocks: vec![],
+                fail_block: None,
+                manifest_error: None,
+            }
+        }
+    }
+
+    impl DfuTransport for MockBootloader {
+        fn control_out(&mut self, request: u8, value: u16, data: &[u8]) -> Result<usize> {
+            match request {
+                DFU_DNLOAD if self.fail_block == Some(value) => {
+                    self.status = DFU_STATUS_ERR_WRITE;
+                    self.state = DFU_STATE_ERROR;
+                }
+                DFU_DNLOAD if data.is_empty() => {
+                    self.state = DFU_STATE_MANIFEST;
+                    self.busy = true;
+                }
+                DFU_DNLOAD => {
+                    self.blocks.push((value, data.to_vec()));
+                    self.state = DFU_STATE_DNBUSY;
+                    self.busy = true;
+                }
+                DFU_CLRSTATUS => {
+                    self.status = DFU_STATUS_OK;
+                    self.state = DFU_STATE_IDLE;
+                }
+                _ => panic!("Unexpected request {}", request),
+            }
+            Ok(data.len())
+        }
+
+        fn control_in(&mut self, request: u8, _value: u16, buf: &mut [u8]) -> Result<usize> {
+            assert_eq!(request, DFU_GETSTATUS);
+            if let (DFU_STATE_MANIFEST, Some(e)) = (self.state, self.manifest_error) {
+                return Err(e.into());
+            }
+
+            let status = [self.status, 0, 0, 0, self.state, 0];
+            buf[..6].copy_from_slice(&status);
+
+            if self.busy {
+                self.busy = false;
+            } else if self.state == DFU_STATE_DNBUSY {
+                self.state = DFU_STATE_DNLOAD_IDLE;
+            } else if self.state == DFU_STATE_MANIFEST {
+                self.state = DFU_STATE_IDLE;
+            }
+            Ok(6)
+        }
+    }
+
+    fn build_image(firmware: &[u8], vendor_id: u16, product_id: u16) -> Vec<u8> {
+        let mut image = firmware.to_vec();
+        image.extend(0x0100u16.to_le_bytes());
+        image.extend(product_id.to_le_bytes());
+        image.extend(vendor_id.to_le_bytes());
+        image.extend(0x011Au16.to_le_bytes());
+        image.extend(b"UFD");
+        image.push(16);
+        let crc = dfu_crc(&image);
+        image.extend(crc.to_le_bytes());
+        image
+    }
+
+    #[test]
+    fn test_verify_image() {
+        let firmware = [1, 2, 3, 4, 5];
+
+        let image = build_image(&firmware, 0x1209, 0x0001);
+        assert_eq!(verify_image(&image, 0x1209, 0x0001).unwrap(), firmware);
+        // Built for another pad
+        assert!(verify_image(&image, 0x1209, 0x0003).is_err());
+
+        let any_device = build_image(&firmware, 0xFFFF, 0xFFFF);
+        assert!(verify_image(&any_device, 0x1209, 0x0001).is_ok());
+
+        let mut corrupted = image.clone();
+        corrupted[0] ^= 0xFF;
+        assert!(verify_image(&corrupted, 0x1209, 0x0001).is_err());
+
+        assert!(verify_image(&firmware, 0x1209, 0x0001).is_err());
+    }
+
+    #[test]
+    fn test_download_in_blocks() {
+        let mut bootloader = MockBootloader::new(DFU_STATE_IDLE);
+        let firmware = (0..10).collect::<Vec<u8>>();
+
+        let mut progress = vec![];
+        download(&mut bootloader, &firmware, 4, |bytes_written| {
+            progress.push(bytes_written)
+        })
+        .unwrap();
+
+        assert_eq!(
+            bootloader.blocks,
+            vec![
+                (0, vec![0, 1, 2, 3]),
+                (1, vec![4, 5, 6, 7]),
+                (2, vec![8, 9]),
+            ]
+        );
+        assert_eq!(progress, vec![4, 8, 10]);
+        assert_eq!(bootloader.state, DFU_STATE_IDLE);
+    }
+
+    #[test]
+    fn test_download_clears_previous_error() {
+        let mut bootloader = MockBootloader::new(DFU_STATE_ERROR);
+        bootloader.status = DFU_STATUS_ERR_WRITE;
+
+        download(&mut bootloader, &[1, 2], 4, |_| {}).unwrap();
+        assert_eq!(bootloader.blocks, vec![(0, vec![1, 2])]);
+    }
+
+    #[test]
+    fn test_download_fails_on_error_status() {
+        let mut bootloader = MockBootloader::new(DFU_STATE_IDLE);
+        bootloader.fail_block = Some(1);
+
+        let mut progress = vec![];
+        let result = download(&mut bootloader, &[0; 8], 4, |bytes_written| {
+            progress.push(bytes_written)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(progress, vec![4]);
+    }
+
+    #[test]
+    fn test_download_while_device_resets() {
+        // Detaching to run the new firmware
+        for e in [rusb::Error::NoDevice, rusb::Error::Io] {
+            let mut bootloader = MockBootloader::new(DFU_STATE_IDLE);
+            bootloader.manifest_error = Some(e);
+            assert!(download(&mut bootloader, &[1, 2], 4, |_| {}).is_ok());
+        }
+
+        let mut bootloader = MockBootloader::new(DFU_STATE_IDLE);
+        bootloader.manifest_error = Some(rusb::Error::Timeout);
+        assert!(download(&mut bootloader, &[1, 2], 4, |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_transfer_size() {
+        assert_eq!(
+            parse_transfer_size(&[9, 0x21, 0x0B, 0xFF, 0x00, 0x00, 0x04]),
+            1024
+        );
+        // Missing or unusable sizes fall back to the default
+        assert_eq!(
+            parse_transfer_size(&[9, 0x21, 0x0B, 0xFF, 0x00, 0x00, 0x00]),
+            64
+        );
+        assert_eq!(parse_transfer_size(&[]), 64);
+
+        let mut bootloader = MockBootloader::new(DFU_STATE_IDLE);
+        assert!(download(&mut bootloader, &[1, 2], 0, |_| {}).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod display_test {
+    use std::collections::HashSet;
+
+    use macropad_console_lib::config::{ApplicationProfile, DisplayConfig, Modifier};
+    use macropad_console_lib::display::{
+        display_reports, render_display, DISPLAY_CLEAR, DISPLAY_REPORT_ID, DISPLAY_REPORT_LENGTH,
+        DISPLAY_SHOW, DISPLAY_TEXT,
+    };
+
+    fn profile() -> ApplicationProfile {
+        serde_json::from_str(
+            r#"{
+                "bindings": [
+                    ["BTN_0", { "displayName": "Copy" }],
+                    ["BTN_1", { "displayName": "Paste" }],
+                    ["BTN_5", { "displayName": "Screenshot" }],
+                    ["BTN_4+BTN_0", { "displayName": "Cut" }],
+                    ["ENC_0_DEC", { "displayName": "Undo" }],
+                    ["ENC_0_INC", { "displayName": "Redo" }]
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_display() {
+        let lines = render_display(
+            &DisplayConfig::default(),
+            Some(("Photoshop.*", &profile())),
+            &HashSet::new(),
+        );
+
+        assert_eq!(
+            lines,
+            vec![
+                "Photoshop.*",
+                "Copy   Paste",
+                "              Screen",
+                "",
+                "",
+                "<Undo >Redo",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_display_layer() {
+        let lines = render_display(
+            &DisplayConfig::default(),
+            Some(("Photoshop.*", &profile())),
+            &HashSet::from_iter(vec![Modifier::Button(4)]),
+        );
+
+        assert_eq!(lines, vec!["Photoshop.*", "Cut", "", "", ""]);
+    }
+
+    #[test]
+    fn test_render_display_fits_config() {
+        let config = DisplayConfig {
+            enabled: true,
+            columns: 6,
+            rows: 2,
+        };
+        let lines = render_display(&config, Some(("Ünïcode profile", &profile())), &HashSet::new());
+
+        assert_eq!(lines, vec!["?n?cod", "C P"]);
+    }
+
+    #[test]
+    fn test_render_display_without_profile() {
+        let lines = render_display(&DisplayConfig::default(), None, &HashSet::new());
+
+        assert_eq!(lines, vec!["No profile"]);
+    }
+
+    #[test]
+    fn test_display_reports() {
+        let long_line = "x".repeat(30);
+        let reports = display_reports(&["Hi".to_string(), long_line]);
+
+        assert!(reports.iter().all(|r| r.len() == DISPLAY_REPORT_LENGTH));
+        assert_eq!(reports.len(), 5);
+        assert_eq!(reports[0][..2], [DISPLAY_REPORT_ID, DISPLAY_CLEAR]);
+        assert_eq!(
+            reports[1][..7],
+            [DISPLAY_REPORT_ID, DISPLAY_TEXT, 0, 0, 2, b'H', b'i']
+        );
+        assert_eq!(reports[2][..5], [DISPLAY_REPORT_ID, DISPLAY_TEXT, 1, 0, 27]);
+        assert_eq!(reports[3][..6], [DISPLAY_REPORT_ID, DISPLAY_TEXT, 1, 27, 3, b'x']);
+        assert_eq!(reports[4][..2], [DISPLAY_REPORT_ID, DISPLAY_SHOW]);
+    }
+}
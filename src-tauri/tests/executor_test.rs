@@ -0,0 +1,222 @@
+#[cfg(test)]
+mod executor_test {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use macropad_console_lib::config::{
+        Action, Command, ConcurrencyPolicy, KeyCombination, Operation,
+    };
+    use macropad_console_lib::executor::{Executor, Job};
+
+    fn button(id: u8) -> KeyCombination {
+        KeyCombination {
+            modifiers: None,
+            action: Action::ButtonPress { id },
+        }
+    }
+
+    // Bound to the button with the same id as `ms`
+    fn delay(ms: u64, concurrency: ConcurrencyPolicy) -> Job {
+        Job::Run {
+            command: Command {
+                display_name: format!("Delay {}", ms),
+                operations: Some(vec![Operation::Delay { ms }]),
+                concurrency,
+                ..Default::default()
+            },
+            binding: Some(button(ms as u8)),
+            repetitions: 1,
+            reverse: false,
+        }
+    }
+
+    fn release(id: u8) -> Job {
+        Job::Release {
+            command: Command::default(),
+            binding: button(id),
+        }
+    }
+
+    fn aborting(operations: Vec<Operation>) -> Job {
+        Job::Run {
+            command: Command {
+                operations: Some(operations),
+                ..Default::default()
+            },
+            binding: None,
+            repetitions: 1,
+            reverse: false,
+        }
+    }
+
+    #[test]
+    fn test_jobs_received_in_order() {
+        let (mut executor, tasks) = Executor::new();
+
+        for ms in 0..5 {
+            assert!(executor
+                .push(delay(ms, ConcurrencyPolicy::Queue), &HashMap::new())
+                .is_none());
+        }
+        drop(executor);
+
+        let delays = tasks
+            .iter()
+            .map(|task| match task.job {
+                Job::Run { command, .. } => command.display_name,
+                Job::Release { .. } => panic!("Unexpected release"),
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(
+            delays,
+            vec!["Delay 0", "Delay 1", "Delay 2", "Delay 3", "Delay 4"]
+        );
+    }
+
+    #[test]
+    fn test_concurrency_policies() {
+        let (mut executor, tasks) = Executor::new();
+
+        // Ignored while the first is still waiting to run
+        executor.push(delay(1, ConcurrencyPolicy::Ignore), &HashMap::new());
+        executor.push(delay(1, ConcurrencyPolicy::Ignore), &HashMap::new());
+        assert_eq!(tasks.try_iter().count(), 1);
+
+        // Restarting cancels the one already scheduled
+        executor.push(delay(2, ConcurrencyPolicy::Restart), &HashMap::new());
+        executor.push(delay(2, ConcurrencyPolicy::Restart), &HashMap::new());
+        let restarted = tasks.try_iter().collect::<Vec<_>>();
+        assert_eq!(restarted.len(), 2);
+        assert!(restarted[0].handle.is_cancelled());
+        assert!(!restarted[1].handle.is_cancelled());
+
+        // Parallel tasks are handed back to be run on their own thread
+        let task = executor
+            .push(delay(3, ConcurrencyPolicy::Parallel), &HashMap::new())
+            .unwrap();
+        assert_eq!(tasks.try_iter().count(), 0);
+
+        // Releases wait for the task while it is running
+        assert!(executor.push(release(3), &HashMap::new()).is_none());
+        assert_eq!(executor.finish(&task.handle).len(), 1);
+
+        // Finished tasks no longer count as running, so releases run straight away
+        assert!(executor.push(release(3), &HashMap::new()).is_some());
+    }
+
+    #[test]
+    fn test_abort_all() {
+        let (mut executor, tasks) = Executor::new();
+
+        executor.push(delay(1, ConcurrencyPolicy::Queue), &HashMap::new());
+        let parallel = executor
+            .push(delay(2, ConcurrencyPolicy::Parallel), &HashMap::new())
+            .unwrap();
+        executor.push(release(1), &HashMap::new());
+
+        executor.abort_all();
+        assert!(parallel.handle.is_cancelled());
+        let queued = tasks.try_iter().collect::<Vec<_>>();
+        assert_eq!(queued.len(), 1);
+        assert!(queued[0].handle.is_cancelled());
+        // Releases still run once the aborted command finishes so keys aren't left held
+        assert_eq!(executor.finish(&queued[0].handle).len(), 1);
+
+        // Aborting commands skip the queue
+        let abort = aborting(vec![Operation::AbortMacros {}]);
+        assert!(executor.push(abort, &HashMap::new()).is_some());
+    }
+
+    #[test]
+    fn test_nested_abort() {
+        let (mut executor, _tasks) = Executor::new();
+        let macros = HashMap::from([
+            ("Stop".to_string(), vec![Operation::AbortMacros {}]),
+            (
+                "Loop".to_string(),
+                vec![Operation::CallMacro {
+                    name: "Loop".to_string(),
+                }],
+            ),
+        ]);
+
+        let call = aborting(vec![Operation::Repeat {
+            times: 2,
+            operations: vec![Operation::CallMacro {
+                name: "Stop".to_string(),
+            }],
+        }]);
+        assert!(executor.push(call, &macros).is_some());
+
+        // Macros calling themselves don't abort, and are looked through once
+        let looping = aborting(vec![Operation::CallMacro {
+            name: "Loop".to_string(),
+        }]);
+        assert!(executor.push(looping, &macros).is_none());
+    }
+
+    #[test]
+    fn test_bindings_with_same_command() {
+        let (mut executor, tasks) = Executor::new();
+
+        // Same command on two buttons, neither ignores nor restarts the other
+        for (policy, ids) in [
+            (ConcurrencyPolicy::Ignore, [1, 2]),
+            (ConcurrencyPolicy::Restart, [3, 4]),
+        ] {
+            for id in ids {
+                let mut job = delay(1, policy);
+                if let Job::Run { binding, .. } = &mut job {
+                    *binding = Some(button(id));
+                }
+                executor.push(job, &HashMap::new());
+            }
+        }
+        let queued = tasks.try_iter().collect::<Vec<_>>();
+        assert_eq!(queued.len(), 4);
+        assert!(queued.iter().all(|task| !task.handle.is_cancelled()));
+    }
+
+    #[test]
+    fn test_release_after_run() {
+        let (mut executor, tasks) = Executor::new();
+
+        // With nothing to wait on, the release runs straight away instead of queueing
+        executor.push(delay(1, ConcurrencyPolicy::Queue), &HashMap::new());
+        assert!(executor.push(release(2), &HashMap::new()).is_some());
+
+        // Otherwise it is handed back once its command finishes, to run on the same thread
+        let parallel = executor
+            .push(delay(2, ConcurrencyPolicy::Parallel), &HashMap::new())
+            .unwrap();
+        assert!(executor.push(release(2), &HashMap::new()).is_none());
+        assert!(executor.push(release(1), &HashMap::new()).is_none());
+        assert_eq!(tasks.try_iter().count(), 1);
+
+        let releases = executor.finish(&parallel.handle);
+        assert!(matches!(
+            releases.as_slice(),
+            [Job::Release { binding, .. }] if *binding == button(2)
+        ));
+        assert!(executor.push(release(2), &HashMap::new()).is_some());
+    }
+
+    #[test]
+    fn test_cancelled_sleep() {
+        let (mut executor, _tasks) = Executor::new();
+        let task = executor
+            .push(delay(0, ConcurrencyPolicy::Parallel), &HashMap::new())
+            .unwrap();
+
+        let handle = task.handle.clone();
+        let sleeper = std::thread::spawn(move || {
+            let started_at = Instant::now();
+            handle.sleep(Duration::from_secs(10));
+            started_at.elapsed()
+        });
+
+        std::thread::sleep(Duration::from_millis(20));
+        executor.abort_all();
+        assert!(sleeper.join().unwrap() < Duration::from_secs(1));
+    }
+}
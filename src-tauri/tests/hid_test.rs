@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod hid_test {
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    use macropad_console_lib::config::{Action, Modifier};
+    use macropad_console_lib::hid::{handle_button, handle_report, handle_report_at, handle_turn};
+    use macropad_console_lib::macropad_state::{MacropadState, ENCODER_MODIFIER_WINDOW};
+
+    #[test]
+    fn test_button_press_and_release() {
+        let (state, action, _) = handle_report(MacropadState::default(), &[0b0001_0000, 0]);
+        assert_eq!(action, Action::ButtonPress { id: 4 });
+
+        let (_, action, _) = handle_report(state, &[0, 0]);
+        assert_eq!(action, Action::ButtonRelease { id: 4 });
+    }
+
+    #[test]
+    fn test_two_bit_encoder_only_fires_from_rest() {
+        let (state, action, steps) = handle_report(MacropadState::default(), &[0, 0b0001_0000]);
+        assert_eq!(action, Action::EncoderIncrement { id: 0 });
+        assert_eq!(steps, 1);
+
+        // Still reporting +1 without returning to 0 is not a new detent
+        let (state, action, _) = handle_report(state, &[0, 0b0001_0000]);
+        assert_eq!(action, Action::None);
+
+        let (state, _, _) = handle_report(state, &[0, 0]);
+        let (_, action, steps) = handle_report(state, &[0, 0b0011_0000]);
+        assert_eq!(action, Action::EncoderDecrement { id: 0 });
+        assert_eq!(steps, 1);
+    }
+
+    #[test]
+    fn test_step_count_encoder() {
+        let (state, action, steps) = handle_report(MacropadState::default(), &[0, 0, 3]);
+        assert_eq!(action, Action::EncoderIncrement { id: 0 });
+        assert_eq!(steps, 3);
+        assert!(state.encoder_turns[0].is_some());
+
+        // Every non-zero step count is a new movement
+        let (state, action, steps) = handle_report(state, &[0, 0, 3]);
+        assert_eq!(action, Action::EncoderIncrement { id: 0 });
+        assert_eq!(steps, 3);
+
+        let (_, action, steps) = handle_report(state, &[0, 0, (-5i8) as u8]);
+        assert_eq!(action, Action::EncoderDecrement { id: 0 });
+        assert_eq!(steps, 5);
+    }
+
+    #[test]
+    fn test_padded_two_bit_report() {
+        // Padded to the full report size by the firmware, which doesn't send step counts
+        let mut report = [0u8; 64];
+        report[1] = 0b0001_0000;
+        let (state, action, steps) = handle_report_at(
+            MacropadState::default(),
+            &report,
+            Some(false),
+            Instant::now(),
+        );
+        assert_eq!(action, Action::EncoderIncrement { id: 0 });
+        assert_eq!(steps, 1);
+
+        report[1] = 0;
+        let (_, action, _) = handle_report_at(state, &report, Some(false), Instant::now());
+        assert_eq!(action, Action::None);
+
+        // Firmware with step counts is trusted even if a report is short
+        let (_, action, _) = handle_report_at(
+            MacropadState::default(),
+            &[0, 0b0001_0000],
+            Some(true),
+            Instant::now(),
+        );
+        assert_eq!(action, Action::None);
+    }
+
+    #[test]
+    fn test_short_report_is_ignored() {
+        let (_, action, _) = handle_report(MacropadState::default(), &[1]);
+        assert_eq!(action, Action::None);
+    }
+
+    #[test]
+    fn test_encoder_push_and_release() {
+        let (state, action, _) = handle_report(MacropadState::default(), &[0, 0b0100_0000]);
+        assert_eq!(action, Action::EncoderPush { id: 0 });
+
+        // Turning while pushed keeps the switch held
+        let (state, action, _) = handle_report(state, &[0, 0b0101_0000]);
+        assert_eq!(action, Action::EncoderIncrement { id: 0 });
+        assert_eq!(
+            state.get_modifiers(&action, Instant::now()),
+            HashSet::from_iter(vec![Modifier::EncoderPush(0)])
+        );
+
+        let (state, _, _) = handle_report(state, &[0, 0b0100_0000]);
+        let (_, action, _) = handle_report(state, &[0, 0]);
+        assert_eq!(action, Action::EncoderRelease { id: 0 });
+    }
+
+    #[test]
+    fn test_encoder_rotation_modifier() {
+        let (state, _, _) = handle_report(MacropadState::default(), &[0, 0, 1]);
+        // Modifiers come from the state before the report that triggered the action
+        let (_, action, _) = handle_report(state, &[0b0010, 0, 0]);
+        assert_eq!(action, Action::ButtonPress { id: 1 });
+
+        let turned_at = state.encoder_turns[0].unwrap().at;
+        assert_eq!(
+            state.get_modifiers(&action, turned_at),
+            HashSet::from_iter(vec![Modifier::EncoderIncrement(0)])
+        );
+        assert!(state
+            .get_modifiers(&action, turned_at + ENCODER_MODIFIER_WINDOW * 2)
+            .is_empty());
+
+        // An encoder's own rotation isn't a modifier for its next detent
+        assert!(state
+            .get_modifiers(&Action::EncoderIncrement { id: 0 }, turned_at)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_keyboard_button_alongside_pad() {
+        let now = Instant::now();
+        let (state, action) = handle_button(MacropadState::default(), 20, true, now);
+        assert_eq!(action, Action::ButtonPress { id: 20 });
+
+        // Reports from the pad leave the keyboard's buttons alone
+        let (state, action, _) = handle_report(state, &[0b0001_0000, 0]);
+        assert_eq!(action, Action::ButtonPress { id: 4 });
+        assert_eq!(
+            state.get_modifiers(&Action::None, now),
+            HashSet::from_iter(vec![Modifier::Button(4), Modifier::Button(20)])
+        );
+
+        // Repeated presses aren't new actions
+        let (state, action) = handle_button(state, 20, true, now);
+        assert_eq!(action, Action::None);
+
+        let (_, action) = handle_button(state, 20, false, now);
+        assert_eq!(action, Action::ButtonRelease { id: 20 });
+    }
+
+    #[test]
+    fn test_turn_outside_report() {
+        let now = Instant::now();
+        let (state, action, steps) = handle_turn(MacropadState::default(), 0, -3, now);
+        assert_eq!(action, Action::EncoderDecrement { id: 0 });
+        assert_eq!(steps, 3);
+        assert_eq!(
+            state.get_modifiers(&Action::None, now),
+            HashSet::from_iter(vec![Modifier::EncoderDecrement(0)])
+        );
+
+        // No movement, and encoders the pad doesn't have, do nothing
+        let (_, action, _) = handle_turn(MacropadState::default(), 0, 0, now);
+        assert_eq!(action, Action::None);
+        let (_, action, _) = handle_turn(MacropadState::default(), 1, 2, now);
+        assert_eq!(action, Action::None);
+    }
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod injector_test {
+    use enigo::{Axis, Button, Coordinate, Direction, InputResult, Key, Keyboard, Mouse};
+    use macropad_console_lib::injector::Injector;
+
+    // Records keys instead of sending them
+    #[derive(Default)]
+    struct RecordingSink {
+        keys: Vec<(Key, Direction)>,
+    }
+
+    impl Keyboard for RecordingSink {
+        fn fast_text(&mut self, _text: &str) -> InputResult<Option<()>> {
+            Ok(Some(()))
+        }
+
+        fn key(&mut self, key: Key, direction: Direction) -> InputResult<()> {
+            self.keys.push((key, direction));
+            Ok(())
+        }
+
+        fn raw(&mut self, _keycode: u16, _direction: Direction) -> InputResult<()> {
+            Ok(())
+        }
+    }
+
+    impl Mouse for RecordingSink {
+        fn button(&mut self, _button: Button, _direction: Direction) -> InputResult<()> {
+            Ok(())
+        }
+
+        fn move_mouse(&mut self, _x: i32, _y: i32, _coordinate: Coordinate) -> InputResult<()> {
+            Ok(())
+        }
+
+        fn scroll(&mut self, _length: i32, _axis: Axis) -> InputResult<()> {
+            Ok(())
+        }
+
+        fn main_display(&self) -> InputResult<(i32, i32)> {
+            Ok((1920, 1080))
+        }
+
+        fn location(&self) -> InputResult<(i32, i32)> {
+            Ok((0, 0))
+        }
+    }
+
+    #[test]
+    fn test_release_all_releases_pressed_keys() {
+        let mut injector = Injector::new(RecordingSink::default());
+        injector.key(Key::Shift, Direction::Press).unwrap();
+        injector.key(Key::Unicode('a'), Direction::Click).unwrap();
+        assert!(injector.is_pressed(Key::Shift));
+
+        injector.release_all();
+        assert!(!injector.is_pressed(Key::Shift));
+        assert_eq!(
+            injector.sink.keys.last(),
+            Some(&(Key::Shift, Direction::Release))
+        );
+
+        // Nothing is left to release
+        injector.release_all();
+        assert_eq!(injector.sink.keys.len(), 3);
+    }
+
+    #[test]
+    fn test_double_press_tracked_once() {
+        let mut injector = Injector::new(RecordingSink::default());
+        injector.key(Key::Control, Direction::Press).unwrap();
+        injector.key(Key::Control, Direction::Press).unwrap();
+
+        injector.release_all();
+        let releases = injector
+            .sink
+            .keys
+            .iter()
+            .filter(|(_, direction)| *direction == Direction::Release)
+            .count();
+        assert_eq!(releases, 1);
+    }
+
+    #[test]
+    fn test_release_unheld_key() {
+        let mut injector = Injector::new(RecordingSink::default());
+        injector.key(Key::Alt, Direction::Press).unwrap();
+
+        // Keys held elsewhere can still be released
+        injector.key(Key::Shift, Direction::Release).unwrap();
+        assert!(injector.is_pressed(Key::Alt));
+        assert_eq!(
+            injector.sink.keys,
+            vec![
+                (Key::Alt, Direction::Press),
+                (Key::Shift, Direction::Release)
+            ]
+        );
+
+        // Unless only keys pressed here are wanted
+        injector.release_held(Key::Shift).unwrap();
+        injector.release_held(Key::Alt).unwrap();
+        injector.release_held(Key::Alt).unwrap();
+        assert!(!injector.is_pressed(Key::Alt));
+        assert_eq!(
+            injector.sink.keys,
+            vec![
+                (Key::Alt, Direction::Press),
+                (Key::Shift, Direction::Release),
+                (Key::Alt, Direction::Release)
+            ]
+        );
+    }
+}
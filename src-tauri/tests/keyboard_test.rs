@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod keyboard_test {
+    use std::collections::HashMap;
+
+    use macropad_console_lib::config::KeyboardConfig;
+    use macropad_console_lib::keyboard::KeyMap;
+
+    fn config() -> KeyboardConfig {
+        KeyboardConfig {
+            device: "Numpad".to_string(),
+            keys: HashMap::from_iter(vec![
+                ("KEY_KP1".to_string(), 12),
+                ("KEY_KP2".to_string(), 13),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_key_map() {
+        let codes = HashMap::from([("KEY_KP1", 79), ("KEY_KP2", 80)]);
+        let key_map = KeyMap::new(&config(), |key| codes.get(key).copied()).unwrap();
+
+        assert_eq!(key_map.get_button(79), Some(12));
+        assert_eq!(key_map.get_button(80), Some(13));
+        assert_eq!(key_map.get_button(81), None);
+
+        assert!(KeyMap::new(&config(), |_| None).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_evdev_key_names() {
+        use macropad_console_lib::keyboard::parse_key;
+
+        let key_map = KeyMap::new(&config(), parse_key).unwrap();
+        assert_eq!(key_map.get_button(79), Some(12));
+        assert_eq!(parse_key("KEY_NOT_A_KEY"), None);
+    }
+}
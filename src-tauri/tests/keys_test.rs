@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod keys_test {
+    use enigo::Key;
+
+    use macropad_console_lib::keys::parse_key;
+
+    #[test]
+    fn test_parse_named_keys() {
+        assert_eq!(parse_key("F5"), Some(Key::F5));
+        assert_eq!(parse_key("enter"), Some(Key::Return));
+        assert_eq!(parse_key("Tab"), Some(Key::Tab));
+        assert_eq!(parse_key("PAGEUP"), Some(Key::PageUp));
+        assert_eq!(parse_key("pgdn"), Some(Key::PageDown));
+        assert_eq!(parse_key("VOLUMEUP"), Some(Key::VolumeUp));
+        assert_eq!(parse_key("RSHIFT"), Some(Key::RShift));
+        assert_eq!(parse_key("ESC"), Some(Key::Escape));
+    }
+
+    #[test]
+    fn test_parse_characters() {
+        assert_eq!(parse_key("A"), Some(Key::Unicode('a')));
+        assert_eq!(parse_key("z"), Some(Key::Unicode('z')));
+        assert_eq!(parse_key("1"), Some(Key::Unicode('1')));
+        assert_eq!(parse_key("/"), Some(Key::Unicode('/')));
+    }
+
+    #[test]
+    fn test_parse_unknown_keys() {
+        // Used to be typed as their first letter
+        assert_eq!(parse_key("F99"), None);
+        assert_eq!(parse_key("VOLUMEUPP"), None);
+        assert_eq!(parse_key("ENTR"), None);
+        assert_eq!(parse_key(""), None);
+    }
+}
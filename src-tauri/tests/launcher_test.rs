@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod launcher_test {
+    use macropad_console_lib::launcher::{check_url, is_allowed};
+
+    #[test]
+    fn test_allow_list() {
+        let allow_list = vec![
+            r"notepad\.exe".to_string(),
+            r"C:\\Tools\\.*".to_string(),
+        ];
+
+        assert!(is_allowed(&allow_list, "notepad.exe"));
+        assert!(is_allowed(&allow_list, r"C:\Tools\build.bat"));
+        // Patterns must match the whole program
+        assert!(!is_allowed(&allow_list, "evil-notepad.exe"));
+        assert!(!is_allowed(&allow_list, r"D:\C:\Tools\build.bat"));
+        assert!(!is_allowed(&[], "notepad.exe"));
+    }
+
+    #[test]
+    fn test_check_url() {
+        assert!(check_url("https://example.com").is_ok());
+        assert!(check_url("HTTP://example.com").is_ok());
+        assert!(check_url("mailto:someone@example.com").is_ok());
+        assert!(check_url("file:///C:/Windows/System32/calc.exe").is_err());
+        assert!(check_url("example.com").is_err());
+    }
+
+    #[cfg(unix)]
+    mod unix {
+        use std::collections::HashMap;
+        use std::time::{Duration, Instant};
+
+        use macropad_console_lib::executor::MacroHandle;
+        use macropad_console_lib::launcher::{build_command, run_and_wait};
+
+        fn sh(script: &str) -> std::process::Command {
+            build_command(
+                "sh",
+                &["-c".to_string(), script.to_string()],
+                &None,
+                &HashMap::from_iter(vec![("GREETING".to_string(), "hello".to_string())]),
+            )
+        }
+
+        #[test]
+        fn test_run_and_wait_captures_output() {
+            let output = run_and_wait(
+                sh("echo $GREETING; echo oops >&2; exit 3"),
+                Duration::from_secs(10),
+                &MacroHandle::default(),
+            )
+            .unwrap();
+
+            assert_eq!(output.exit_code, Some(3));
+            assert_eq!(output.stdout, "hello\n");
+            assert_eq!(output.stderr, "oops\n");
+        }
+
+        #[test]
+        fn test_run_and_wait_timeout() {
+            let started_at = Instant::now();
+            let result = run_and_wait(
+                sh("sleep 10"),
+                Duration::from_millis(100),
+                &MacroHandle::default(),
+            );
+
+            assert!(result.unwrap_err().to_string().contains("Timed out"));
+            assert!(started_at.elapsed() < Duration::from_secs(5));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+#[cfg(test)]
+mod macropad_state_test {
+    use std::time::{Duration, Instant};
+
+    use macropad_console_lib::macropad_state::CycleStates;
+
+    #[test]
+    fn test_cycle_wraps() {
+        let mut cycles = CycleStates::default();
+        let now = Instant::now();
+
+        let steps = (0..4)
+            .map(|_| cycles.step("brush", 3, false, None, now).unwrap())
+            .collect::<Vec<usize>>();
+        assert_eq!(steps, vec![0, 1, 2, 0]);
+
+        assert_eq!(cycles.step("brush", 3, true, None, now), Some(2));
+        assert_eq!(cycles.step("brush", 3, true, None, now), Some(1));
+        // Other cycles keep their own position
+        assert_eq!(cycles.step("tool", 3, true, None, now), Some(2));
+        assert_eq!(cycles.step("empty", 0, false, None, now), None);
+    }
+
+    #[test]
+    fn test_cycle_reset() {
+        let mut cycles = CycleStates::default();
+        let now = Instant::now();
+        let reset_after = Some(Duration::from_millis(500));
+
+        assert_eq!(cycles.step("brush", 3, false, reset_after, now), Some(0));
+        let now = now + Duration::from_millis(400);
+        assert_eq!(cycles.step("brush", 3, false, reset_after, now), Some(1));
+        let now = now + Duration::from_millis(600);
+        assert_eq!(cycles.step("brush", 3, false, reset_after, now), Some(0));
+    }
+}
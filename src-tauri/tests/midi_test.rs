@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod midi_test {
+    use std::collections::HashMap;
+
+    use macropad_console_lib::config::{MidiController, MidiControllerMode, MidiDeviceConfig};
+    use macropad_console_lib::midi::MidiMapper;
+
+    fn config(mode: MidiControllerMode) -> MidiDeviceConfig {
+        MidiDeviceConfig {
+            port: "Surface".to_string(),
+            channel: Some(1),
+            notes: HashMap::from_iter(vec![(60, 4), (61, 12)]),
+            controllers: HashMap::from_iter(vec![(16, MidiController { encoder: 0, mode })]),
+        }
+    }
+
+    #[test]
+    fn test_notes_map_to_buttons() {
+        let mut mapper = MidiMapper::new(config(MidiControllerMode::Relative));
+
+        assert_eq!(mapper.map(&[0x90, 60, 100]), Some(vec![0x10, 0, 0]));
+        // Note on without velocity
+        assert_eq!(mapper.map(&[0x90, 60, 0]), Some(vec![0, 0, 0]));
+        assert_eq!(mapper.map(&[0x90, 60, 100]), Some(vec![0x10, 0, 0]));
+        assert_eq!(mapper.map(&[0x80, 60, 64]), Some(vec![0, 0, 0]));
+
+        // Unmapped note, button id out of range and other channel
+        assert_eq!(mapper.map(&[0x90, 62, 100]), None);
+        assert_eq!(mapper.map(&[0x90, 61, 100]), None);
+        assert_eq!(mapper.map(&[0x91, 60, 100]), None);
+    }
+
+    #[test]
+    fn test_relative_controllers() {
+        let mut mapper = MidiMapper::new(config(MidiControllerMode::Relative));
+        assert_eq!(mapper.map(&[0xB0, 16, 2]), Some(vec![0, 0, 2]));
+        assert_eq!(mapper.map(&[0xB0, 16, 127]), Some(vec![0, 0, (-1i8) as u8]));
+
+        let mut mapper = MidiMapper::new(config(MidiControllerMode::RelativeOffset));
+        assert_eq!(mapper.map(&[0xB0, 16, 65]), Some(vec![0, 0, 1]));
+        assert_eq!(mapper.map(&[0xB0, 16, 61]), Some(vec![0, 0, (-3i8) as u8]));
+        assert_eq!(mapper.map(&[0xB0, 16, 64]), None);
+    }
+
+    #[test]
+    fn test_absolute_controllers() {
+        let mut mapper = MidiMapper::new(config(MidiControllerMode::Absolute));
+
+        // The first value is where the control starts
+        assert_eq!(mapper.map(&[0xB0, 16, 50]), None);
+        assert_eq!(mapper.map(&[0xB0, 16, 53]), Some(vec![0, 0, 3]));
+        assert_eq!(mapper.map(&[0xB0, 16, 52]), Some(vec![0, 0, (-1i8) as u8]));
+        assert_eq!(mapper.map(&[0xB0, 17, 52]), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs the ALSA sequencer"]
+    fn test_midi_transport_with_virtual_port() {
+        use std::time::{Duration, Instant};
+
+        use macropad_console_lib::midi::MidiTransport;
+        use macropad_console_lib::transport::Transport;
+        use midir::os::unix::VirtualOutput;
+        use midir::MidiOutput;
+
+        let mut surface = MidiOutput::new("macropad-console-test")
+            .unwrap()
+            .create_virtual("Test Surface")
+            .unwrap();
+        let mut config = config(MidiControllerMode::Relative);
+        config.port = "Test Surface".to_string();
+        let mut transport = MidiTransport::connect(&[config]).unwrap().unwrap();
+
+        surface.send(&[0x90, 60, 100]).unwrap();
+        surface.send(&[0xB0, 16, 1]).unwrap();
+
+        let mut reports = vec![];
+        let mut buf = [0u8; 64];
+        let started_at = Instant::now();
+        while reports.len() < 2 && started_at.elapsed() < Duration::from_secs(5) {
+            let n_bytes = transport.read(&mut buf).unwrap();
+            if n_bytes > 0 {
+                reports.push(buf[..n_bytes].to_vec());
+            }
+        }
+        assert_eq!(reports, vec![vec![0x10, 0, 0], vec![0x10, 0, 1]]);
+    }
+}
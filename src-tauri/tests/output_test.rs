@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod output_test {
+    use std::collections::HashMap;
+
+    use macropad_console_lib::config::LedState;
+    use macropad_console_lib::output::{led_report, OutputQueue, LED_REPORT_ID};
+
+    #[test]
+    fn test_led_report() {
+        let report = led_report(&HashMap::from_iter(vec![
+            (0, LedState::On),
+            (2, LedState::Rgb { r: 1, g: 2, b: 3 }),
+            (11, LedState::Off),
+        ]));
+
+        assert_eq!(report.len(), 1 + 12 * 3);
+        assert_eq!(report[0], LED_REPORT_ID);
+        assert_eq!(report[1..4], [0xFF, 0xFF, 0xFF]);
+        assert_eq!(report[4..7], [0, 0, 0]);
+        assert_eq!(report[7..10], [1, 2, 3]);
+        assert!(report[10..].iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn test_unchanged_leds_are_not_resent() {
+        let mut queue = OutputQueue::default();
+        let report = led_report(&HashMap::new());
+
+        queue.push_leds(report.clone());
+        queue.push_leds(report.clone());
+        assert_eq!(queue.drain(), vec![report.clone()]);
+
+        queue.push_leds(report.clone());
+        assert!(queue.drain().is_empty());
+
+        // A reconnected device needs the LEDs again
+        queue.reset();
+        queue.push_leds(report.clone());
+        assert_eq!(queue.drain(), vec![report]);
+    }
+}
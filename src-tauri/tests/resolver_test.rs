@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod resolver_test {
+    use std::time::{Duration, Instant};
+
+    use macropad_console_lib::config::{Action, ApplicationProfile};
+    use macropad_console_lib::hid::handle_button;
+    use macropad_console_lib::macropad_state::{EncoderTurn, MacropadState};
+    use macropad_console_lib::resolver::{resolve_action, PressedCombinations};
+
+    fn profile() -> ApplicationProfile {
+        serde_json::from_str::<ApplicationProfile>(
+            r#"{
+                "bindings": [
+                    ["ENC_0_PUSH", { "displayName": "Push" }],
+                    ["BTN_7", { "displayName": "Copy" }],
+                    ["BTN_4+BTN_7", { "displayName": "Paste" }],
+                    ["ENC_0_INC+BTN_8", { "displayName": "Next" }],
+                    ["BTN_8", { "displayName": "Select" }]
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    // The state just after the encoder was turned up
+    fn turned(now: Instant) -> MacropadState {
+        let mut macropad_state = MacropadState::default();
+        macropad_state.encoder_turns[0] = Some(EncoderTurn {
+            at: now - Duration::from_millis(100),
+            direction: 1,
+        });
+        macropad_state
+    }
+
+    fn resolve(
+        macropad_state: &MacropadState,
+        pressed_combinations: &mut PressedCombinations,
+        action: Action,
+        now: Instant,
+    ) -> (Option<String>, bool) {
+        let resolution = resolve_action(
+            &profile(),
+            macropad_state,
+            pressed_combinations,
+            action,
+            1,
+            now,
+        )
+        .unwrap();
+        (
+            resolution.command.map(|command| command.display_name),
+            resolution.release,
+        )
+    }
+
+    #[test]
+    fn test_push_after_turn() {
+        let now = Instant::now();
+        let mut pressed_combinations = PressedCombinations::default();
+        let macropad_state = turned(now);
+
+        // Its own turn doesn't modify the push
+        assert_eq!(
+            resolve(
+                &macropad_state,
+                &mut pressed_combinations,
+                Action::EncoderPush { id: 0 },
+                now
+            ),
+            (Some("Push".to_string()), false)
+        );
+        // Without a binding for the turn, the button isn't modified by it
+        assert_eq!(
+            resolve(
+                &macropad_state,
+                &mut pressed_combinations,
+                Action::ButtonPress { id: 7 },
+                now
+            ),
+            (Some("Copy".to_string()), false)
+        );
+        assert_eq!(
+            resolve(
+                &macropad_state,
+                &mut pressed_combinations,
+                Action::ButtonPress { id: 8 },
+                now
+            ),
+            (Some("Next".to_string()), false)
+        );
+    }
+
+    #[test]
+    fn test_release_after_turn() {
+        let now = Instant::now();
+        let mut pressed_combinations = PressedCombinations::default();
+
+        let (macropad_state, action) = handle_button(MacropadState::default(), 8, true, now);
+        assert_eq!(
+            resolve(
+                &MacropadState::default(),
+                &mut pressed_combinations,
+                action,
+                now
+            ),
+            (Some("Select".to_string()), false)
+        );
+
+        // Released just after the encoder was turned
+        let mut turned_state = turned(now);
+        turned_state.buttons = macropad_state.buttons;
+        let (_, action) = handle_button(turned_state, 8, false, now);
+        assert_eq!(
+            resolve(&turned_state, &mut pressed_combinations, action, now),
+            (Some("Select".to_string()), true)
+        );
+    }
+
+    #[test]
+    fn test_release_after_modifier_released() {
+        let now = Instant::now();
+        let mut pressed_combinations = PressedCombinations::default();
+
+        let (macropad_state, _) = handle_button(MacropadState::default(), 4, true, now);
+        let (held_state, action) = handle_button(macropad_state, 7, true, now);
+        assert_eq!(
+            resolve(&macropad_state, &mut pressed_combinations, action, now),
+            (Some("Paste".to_string()), false)
+        );
+
+        // BTN_4 is let go first, BTN_7 still releases what it pressed
+        let (macropad_state, _) = handle_button(held_state, 4, false, now);
+        let (_, action) = handle_button(macropad_state, 7, false, now);
+        assert_eq!(
+            resolve(&macropad_state, &mut pressed_combinations, action, now),
+            (Some("Paste".to_string()), true)
+        );
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod serial_test {
+    use macropad_console_lib::serial::{SerialParser, FRAME_START};
+
+    fn reports(parser: &mut SerialParser) -> Vec<Vec<u8>> {
+        let mut reports = vec![];
+        while let Some(report) = parser.next_report() {
+            reports.push(report.unwrap());
+        }
+        reports
+    }
+
+    #[test]
+    fn test_text_events() {
+        let mut parser = SerialParser::default();
+        parser.push(b"D 4\r\nE 0 -2\nP 0\nU 4\nR 0\n");
+
+        assert_eq!(
+            reports(&mut parser),
+            vec![
+                vec![0b0001_0000, 0, 0],
+                vec![0b0001_0000, 0, (-2i8) as u8],
+                vec![0b0001_0000, 0b0100_0000, 0],
+                vec![0, 0b0100_0000, 0],
+                vec![0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_split_across_reads() {
+        let mut parser = SerialParser::default();
+
+        parser.push(b"D ");
+        assert!(parser.next_report().is_none());
+        parser.push(&[b'1', b'\n', FRAME_START, 2, 0x10]);
+        assert_eq!(parser.next_report().unwrap().unwrap(), vec![0b0010, 0, 0]);
+        assert!(parser.next_report().is_none());
+
+        // Text events carry on from the buttons in a binary report
+        parser.push(&[0x00, b'D', b' ', b'0', b'\n']);
+        assert_eq!(
+            reports(&mut parser),
+            vec![vec![0x10, 0x00], vec![0x11, 0x00, 0]]
+        );
+    }
+
+    #[test]
+    fn test_invalid_event() {
+        let mut parser = SerialParser::default();
+        parser.push(b"D 12\nX\nD 0\n");
+
+        assert!(parser.next_report().unwrap().is_err());
+        assert!(parser.next_report().unwrap().is_err());
+        assert_eq!(parser.next_report().unwrap().unwrap(), vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn test_invalid_encoder() {
+        let mut parser = SerialParser::default();
+        parser.push(b"E 4000000000 1\nE 18446744073709551615 1\nE 1 1\nP 1\nE 0 -2\n");
+
+        // The pad has a single encoder and encoder switch
+        for _ in 0..4 {
+            assert!(parser.next_report().unwrap().is_err());
+        }
+        assert_eq!(
+            parser.next_report().unwrap().unwrap(),
+            vec![0, 0, (-2i8) as u8]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serial_transport_over_pty() {
+        use std::io::{Read, Write};
+        use std::time::{Duration, Instant};
+
+        use macropad_console_lib::device_info::DeviceInfo;
+        use macropad_console_lib::serial::SerialTransport;
+        use macropad_console_lib::transport::Transport;
+        use serialport::{SerialPort, TTYPort};
+
+        let (mut pad, mut port) = TTYPort::pair().unwrap();
+        port.set_timeout(Duration::from_millis(1)).unwrap();
+        pad.set_timeout(Duration::from_secs(1)).unwrap();
+        let mut transport = SerialTransport::new(Box::new(port), DeviceInfo::default());
+
+        pad.write_all(b"D 4\nE 0 3\n").unwrap();
+
+        let mut reports = vec![];
+        let mut buf = [0u8; 64];
+        let started_at = Instant::now();
+        while reports.len() < 2 && started_at.elapsed() < Duration::from_secs(5) {
+            let n_bytes = transport.read(&mut buf).unwrap();
+            if n_bytes > 0 {
+                reports.push(buf[..n_bytes].to_vec());
+            }
+        }
+        assert_eq!(reports, vec![vec![0x10, 0, 0], vec![0x10, 0, 3]]);
+
+        transport.write(&[0x02, 0xFF]).unwrap();
+        let mut frame = [0u8; 4];
+        pad.read_exact(&mut frame).unwrap();
+        assert_eq!(frame, [FRAME_START, 2, 0x02, 0xFF]);
+    }
+}
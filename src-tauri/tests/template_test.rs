@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod template_test {
+    use chrono::{Local, TimeZone};
+
+    use macropad_console_lib::template::{render_template, validate_template, TemplateContext};
+
+    fn context() -> TemplateContext {
+        TemplateContext {
+            now: Local.with_ymd_and_hms(2024, 5, 1, 13, 45, 0).unwrap(),
+            window_title: "notes.txt - Notepad".to_string(),
+            app_name: "notepad.exe".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let context = context();
+
+        assert_eq!(
+            render_template("Hello, wörld ✓\nLine two", &context).unwrap(),
+            "Hello, wörld ✓\nLine two"
+        );
+        assert_eq!(
+            render_template("{date} {time}", &context).unwrap(),
+            "2024-05-01 13:45:00"
+        );
+        assert_eq!(
+            render_template("{datetime}", &context).unwrap(),
+            "2024-05-01 13:45:00"
+        );
+        assert_eq!(
+            render_template("{date:%d/%m/%Y}", &context).unwrap(),
+            "01/05/2024"
+        );
+        assert_eq!(
+            render_template("From {window} ({app})", &context).unwrap(),
+            "From notes.txt - Notepad (notepad.exe)"
+        );
+        assert_eq!(
+            render_template("{{date}} }}", &context).unwrap(),
+            "{date} }"
+        );
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        assert!(validate_template("{date").is_err());
+        assert!(validate_template("date}").is_err());
+        assert!(validate_template("{weekday}").is_err());
+        assert!(validate_template("{window:upper}").is_err());
+        assert!(validate_template("{date:%Q}").is_err());
+        assert!(validate_template("{date:%A}").is_ok());
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(test)]
+mod toggle_test {
+    use std::collections::HashSet;
+
+    use macropad_console_lib::toggle::ToggleStates;
+
+    #[test]
+    fn test_flip() {
+        let mut toggles = ToggleStates::default();
+        assert!(!toggles.is_on("mute"));
+
+        assert!(toggles.flip("mute"));
+        assert!(toggles.is_on("mute"));
+        assert!(!toggles.is_on("record"));
+
+        assert!(!toggles.flip("mute"));
+        assert!(!toggles.is_on("mute"));
+    }
+
+    #[test]
+    fn test_turn_off() {
+        let mut toggles = ToggleStates::default();
+        toggles.flip("hold shift");
+        toggles.flip("mute");
+
+        let ids = HashSet::from(["hold shift".to_string(), "hold ctrl".to_string()]);
+        assert_eq!(toggles.turn_off(&ids), vec!["hold shift"]);
+        assert!(!toggles.is_on("hold shift"));
+        assert!(toggles.is_on("mute"));
+        assert!(toggles.turn_off(&ids).is_empty());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut toggles = ToggleStates::default();
+        toggles.flip("mute");
+
+        let json = serde_json::to_string(&toggles).unwrap();
+        assert_eq!(json, r#"{"toggles":{"mute":true}}"#);
+        let toggles = serde_json::from_str::<ToggleStates>(&json).unwrap();
+        assert!(toggles.is_on("mute"));
+        assert!(serde_json::from_str::<ToggleStates>("{}")
+            .unwrap()
+            .toggles
+            .is_empty());
+    }
+}
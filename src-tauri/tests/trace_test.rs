@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod trace_test {
+    use macropad_console_lib::config::{Action, ApplicationProfile, Command, DebounceConfig};
+    use macropad_console_lib::trace::{format_report, parse_trace, replay_trace, TraceEntry};
+
+    #[test]
+    fn test_parse_trace() {
+        let entries = parse_trace("# Session\n1000 10 00\n1050 00 00 ff\n").unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                TraceEntry {
+                    timestamp: 1000,
+                    report: vec![0x10, 0x00],
+                    step_counts: None,
+                },
+                TraceEntry {
+                    timestamp: 1050,
+                    report: vec![0x00, 0x00, 0xFF],
+                    step_counts: None,
+                },
+            ]
+        );
+
+        assert_eq!(format_report(&entries[1].report), "00 00 ff");
+        assert!(parse_trace("1000 10 00\nsoon 10 00\n").is_err());
+        assert!(parse_trace("# step counts: maybe\n").is_err());
+    }
+
+    #[test]
+    fn test_replay_uses_recorded_step_counts() {
+        let profile = ApplicationProfile::default();
+        // A padded report carrying the 2 bit encoder value
+        let trace = "1000 00 10 00\n";
+
+        // Without a header the padding is taken for a step count
+        let entries = parse_trace(trace).unwrap();
+        let steps = replay_trace(&entries, DebounceConfig::default(), &profile);
+        assert_eq!(steps[0].action, Action::None);
+
+        let entries = parse_trace(&format!("# step counts: false\n{}", trace)).unwrap();
+        assert_eq!(entries[0].step_counts, Some(false));
+        let steps = replay_trace(&entries, DebounceConfig::default(), &profile);
+        assert_eq!(steps[0].action, Action::EncoderIncrement { id: 0 });
+    }
+
+    #[test]
+    fn test_replay_trace_resolves_modified_encoder() {
+        let profile = serde_json::from_str::<ApplicationProfile>(
+            r#"{
+                "bindings": [
+                    ["BTN_4+ENC_0_INC", { "displayName": "Zoom in" }]
+                ]
+            }"#,
+        )
+        .unwrap();
+        let entries = parse_trace("1000 10 00 00\n1100 10 00 02\n1200 00 00 00\n").unwrap();
+
+        let steps = replay_trace(&entries, DebounceConfig::default(), &profile);
+        let actions = steps
+            .iter()
+            .map(|step| step.action.clone())
+            .collect::<Vec<Action>>();
+        assert_eq!(
+            actions,
+            vec![
+                Action::ButtonPress { id: 4 },
+                Action::EncoderIncrement { id: 0 },
+                Action::ButtonRelease { id: 4 },
+            ]
+        );
+
+        let resolution = steps[1].resolution.as_ref().unwrap();
+        assert_eq!(
+            resolution
+                .command
+                .as_ref()
+                .map(|command: &Command| command.display_name.as_str()),
+            Some("Zoom in")
+        );
+        assert_eq!(resolution.repetitions, 2);
+        assert!(steps[0].resolution.as_ref().unwrap().command.is_none());
+    }
+}
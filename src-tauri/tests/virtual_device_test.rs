@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod virtual_device_test {
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    use macropad_console_lib::transport::Transport;
+    use macropad_console_lib::virtual_device::{
+        frame_report, parse_script, take_report, ScriptStep, SocketTransport,
+    };
+
+    #[test]
+    fn test_parse_script() {
+        let steps = parse_script(
+            "# Press and release button 4\n\
+             10 00\n\
+             wait 50\n\
+             \n\
+             0x00 0x00 0xFF\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            steps,
+            vec![
+                ScriptStep::Report(vec![0x10, 0x00]),
+                ScriptStep::Wait(Duration::from_millis(50)),
+                ScriptStep::Report(vec![0x00, 0x00, 0xFF]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_script_reports_line_of_error() {
+        let e = parse_script("10 00\nwait soon\n").unwrap_err();
+        assert!(e.to_string().starts_with("Line 2:"));
+
+        assert!(parse_script("100 00").is_err());
+    }
+
+    #[test]
+    fn test_take_report() {
+        let mut pending = frame_report(&[0x10, 0x00]).unwrap();
+        pending.extend(frame_report(&[0, 0, 3]).unwrap());
+        pending.push(2);
+
+        assert_eq!(take_report(&mut pending), Some(vec![0x10, 0x00]));
+        assert_eq!(take_report(&mut pending), Some(vec![0, 0, 3]));
+        // The rest of the report hasn't arrived yet
+        assert_eq!(take_report(&mut pending), None);
+        pending.push(0x10);
+        assert_eq!(take_report(&mut pending), None);
+        pending.push(0x00);
+        assert_eq!(take_report(&mut pending), Some(vec![0x10, 0x00]));
+        assert!(pending.is_empty());
+
+        assert!(frame_report(&[0; 256]).is_err());
+    }
+
+    #[test]
+    fn test_socket_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut client = TcpStream::connect(&address).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut transport = SocketTransport::new(stream, &address).unwrap();
+
+        client.write_all(&[3, 0, 0, 0xFB, 2, 0x10]).unwrap();
+        client.write_all(&[0x00]).unwrap();
+
+        let mut reports = vec![];
+        let mut buf = [0u8; 64];
+        let started_at = Instant::now();
+        while reports.len() < 2 && started_at.elapsed() < Duration::from_secs(5) {
+            let n_bytes = transport.read(&mut buf).unwrap();
+            if n_bytes > 0 {
+                reports.push(buf[..n_bytes].to_vec());
+            }
+        }
+        assert_eq!(reports, vec![vec![0, 0, 0xFB], vec![0x10, 0x00]]);
+    }
+}
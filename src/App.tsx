@@ -1,7 +1,7 @@
 import { useEffect, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from '@tauri-apps/api/event';
-import { ActiveWindow, ApplicationConfig } from "./types"
+import { ActiveWindow, ApplicationConfig, LoadedConfig } from "./types"
 import ApplicationConfigElement from "./ApplicationConfigElement";
 import CssBaseline from "@mui/material/CssBaseline";
 import Button from "@mui/material/Button";
@@ -15,6 +15,7 @@ function App() {
     appName: "",
   });
   const [applicationConfig, setApplicationConfig] = useState<ApplicationConfig | undefined>();
+  const [configErrors, setConfigErrors] = useState<Array<string>>([]);
   useEffect(() => {
     listen<ActiveWindow>('active-window-changed', (event) => {
       console.log(event);
@@ -25,7 +26,14 @@ function App() {
   const getConfig = async () => {
     return invoke<string>('get_config').then((configJson) => {
       console.log(configJson);
-      setApplicationConfig(JSON.parse(configJson) as ApplicationConfig);
+      const loadedConfig = JSON.parse(configJson) as LoadedConfig;
+      setApplicationConfig(loadedConfig.config);
+      setConfigErrors(loadedConfig.errors);
+    }).catch((e) => {
+      // Nothing is shown to edit, so the file isn't saved over
+      console.error(e);
+      setApplicationConfig(undefined);
+      setConfigErrors([`Failed to load config: ${e}`]);
     });
   };
 
@@ -34,7 +42,14 @@ function App() {
   }, [])
 
   const saveConfig = async () => {
-    await invoke('save_config', { configJson: JSON.stringify(applicationConfig) });
+    try {
+      await invoke('save_config', { configJson: JSON.stringify(applicationConfig) });
+    } catch (e) {
+      // Invalid configs, e.g. with unknown key names, are rejected
+      console.error(e);
+      alert(`Failed to save config: ${e}`);
+      return;
+    }
     await getConfig();
   };
 
@@ -47,6 +62,9 @@ function App() {
           <Typography variant="body1">{activeWindow.appName}</Typography>
         </Box>
         <Button onClick={getConfig}>Reload Config</Button>
+        {configErrors.map((error, index) => (
+          <Typography key={index} variant="body2" color="error">{error}</Typography>
+        ))}
         {applicationConfig && <ApplicationConfigElement applicationConfig={applicationConfig} saveConfig={saveConfig} />}
       </Box>
     </>
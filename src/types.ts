@@ -5,10 +5,88 @@ export type ActiveWindow = {
 
 export type ApplicationConfig = {
   applicationProfiles: {[key: string]:  ApplicationProfile};
+  debounce?: DebounceConfig;
+  display?: DisplayConfig;
+  serial?: SerialConfig | null;
+  midiDevices?: Array<MidiDeviceConfig>;
+  keyboards?: Array<KeyboardConfig>;
+  launch?: LaunchConfig;
+  // Operations shared between profiles, run with callMacro
+  macros?: {[name: string]: Array<Operation>};
+}
+
+// The config as written, with why any parts of it are skipped when used
+// Saving is rejected until the errors are fixed
+export type LoadedConfig = {
+  config: ApplicationConfig;
+  errors: Array<string>;
+}
+
+// Limits on what run and openPath operations may start
+export type LaunchConfig = {
+  // Regexes matching the whole program or path, nothing is allowed when empty
+  allowList: Array<string>;
+  timeoutMs: number;
+}
+
+// Windows are in milliseconds, 0 disables filtering
+export type DebounceConfig = {
+  buttonMs: number;
+  buttons: {[key: number]: number};
+  encoderReversalMs: number;
+}
+
+// Size of the display in characters
+export type DisplayConfig = {
+  enabled: boolean;
+  columns: number;
+  rows: number;
+}
+
+// Takes effect on restart
+export type SerialConfig = {
+  port: string;
+  baudRate?: number;
+}
+
+// Linux only, takes effect on restart
+export type KeyboardConfig = {
+  // Regex matched against the input device name
+  device: string;
+  // Key name, e.g. "KEY_KP1", to button id
+  keys: {[key: string]: number};
+}
+
+// Takes effect on restart
+export type MidiDeviceConfig = {
+  // Regex matched against the MIDI port name
+  port: string;
+  // 1 to 16
+  channel?: number | null;
+  // Note number to button id, 0 to 11
+  notes?: {[note: number]: number};
+  // Controller number to encoder
+  controllers?: {[controller: number]: MidiController};
+}
+
+export type MidiController = {
+  encoder: number;
+  mode?: 'absolute' | 'relative' | 'relativeOffset';
 }
 
 export type ApplicationProfile = {
   bindings: Array<[string, Command]>
+  leds?: {[key: number]: LedState};
+  // Keyed by modifiers, e.g. "BTN_4+ENC_0_PUSH"
+  layers?: {[key: string]: {[key: number]: LedState}};
+}
+
+export type LedState = "off" | "on" | {
+  rgb: {
+    r: number;
+    g: number;
+    b: number;
+  }
 }
 
 // Actions
@@ -20,6 +98,9 @@ export type Action = {
   buttonPress?: {
     id: ButtonIds;
   }
+  encoderPush?: {
+    id: EncoderIds;
+  }
   encoderIncrement?: {
     id: EncoderIds;
   }
@@ -32,8 +113,45 @@ export type Command = {
   displayName: string;
   radialMenuItems?: Array<RadialMenuItem>;
   operations?: Array<Operation>;
+  acceleration?: Array<AccelerationStep>;
+  concurrency?: ConcurrencyPolicy;
+  // Used instead of the operations when set
+  toggle?: Toggle;
+  // Used instead of the operations when set
+  cycle?: Cycle;
+}
+
+// Runs the next list of operations on each press, wrapping around, encoder decrements step backwards
+export type Cycle = {
+  id: string;
+  steps: Array<Array<Operation>>;
+  // Starts over from the first step after this long without a press
+  resetAfterMs?: number;
+}
+
+// Alternates between running on and off, toggles with the same id share their state
+export type Toggle = {
+  id: string;
+  on: Array<Operation>;
+  off: Array<Operation>;
+  led?: ToggleLed;
 }
 
+export type ToggleLed = {
+  button: number;
+  on: LedState;
+  off?: LedState;
+}
+
+// What happens when a command is triggered while it is still running
+export type ConcurrencyPolicy = "ignore" | "restart" | "queue" | "parallel";
+
+export type AccelerationStep = {
+  minSpeed: number;
+  multiplier: number;
+}
+
+// Key names are listed in src-tauri/src/keys.rs, other single characters are typed as they are
 export type Operation = {
   keyPress?: {
     key: string;
@@ -44,6 +162,14 @@ export type Operation = {
   keyRelease?: {
     key: string;
   };
+  // Placeholders: {date}, {time}, {datetime}, {date:<strftime format>}, {window}, {app}
+  typeText?: {
+    text: string;
+  };
+  // e.g. "Ctrl+Shift+T", the last key is tapped while the others are held
+  shortcut?: {
+    keys: string;
+  };
   delay?: {
     ms: number;
   };
@@ -51,8 +177,103 @@ export type Operation = {
     times: number;
     operations: Array<Operation>;
   };
+  mouseClick?: {
+    button?: MouseButton;
+  };
+  // Relative moves are from the current position, otherwise to a position on the screen
+  mouseMove?: {
+    x: number;
+    y: number;
+    relative?: boolean;
+  };
+  mouseDrag?: {
+    button?: MouseButton;
+    x: number;
+    y: number;
+    relative?: boolean;
+  };
+  // In wheel notches, positive amounts scroll down or right
+  scroll?: {
+    axis?: "vertical" | "horizontal";
+    amount: number;
+  };
+  // Programs must be in the launch allow list
+  run?: {
+    program: string;
+    args?: Array<string>;
+    cwd?: string | null;
+    env?: {[key: string]: string};
+    wait?: boolean;
+  };
+  openUrl?: {
+    url: string;
+  };
+  openPath?: {
+    path: string;
+  };
+  // Paste with a "Ctrl+V" shortcut afterwards, and delay before restoring so the app has read it
+  clipboardSet?: {
+    text: string;
+  };
+  clipboardSave?: {};
+  clipboardRestore?: {};
+  clipboardTransform?: {
+    kind: TextTransform;
+  };
+  callMacro?: {
+    name: string;
+  };
+  abortMacros?: {};
+  // Runs then if the condition holds when it is reached, otherwise else
+  if?: {
+    condition: Condition;
+    then: Array<Operation>;
+    else?: Array<Operation>;
+  };
+  // Sets a variable for conditions to test
+  setVariable?: {
+    name: string;
+    value: string;
+  };
 }
 
+export type Condition = {
+  // Regexes searched for in the active window's title and executable name
+  windowTitle?: {
+    pattern: string;
+  };
+  appName?: {
+    pattern: string;
+  };
+  // An input written as in bindings, e.g. "BTN_4" or "ENC_0_PUSH"
+  held?: {
+    input: string;
+  };
+  toggleOn?: {
+    id: string;
+  };
+  variable?: {
+    name: string;
+    value: string;
+  };
+  // "HH:MM", wraps past midnight when end is earlier than start
+  timeBetween?: {
+    start: string;
+    end: string;
+  };
+  not?: Condition;
+  all?: Array<Condition>;
+  any?: Array<Condition>;
+}
+
+export type TextTransform =
+  | "uppercase"
+  | "lowercase"
+  | "trim"
+  | { regexReplace: { pattern: string; replacement: string } };
+
+export type MouseButton = "left" | "middle" | "right" | "back" | "forward";
+
 export type RadialMenuItem = {
   label: string;
   command: Command;
@@ -64,3 +285,50 @@ export type ShowRadialMenuEvent = {
   location: [number, number];
   items: Array<RadialMenuItem>;
 };
+
+// Payload of the device-connected event and the get_device_info command
+export type DeviceInfo = {
+  manufacturer: string | null;
+  product: string | null;
+  serialNumber: string | null;
+  firmware: FirmwareInfo | null;
+  supported: boolean;
+};
+
+export type FirmwareInfo = {
+  version: {
+    major: number;
+    minor: number;
+    patch: number;
+  };
+  layout: {
+    buttons: number;
+    encoders: number;
+    columns: number;
+    rgbLeds: boolean;
+    display: boolean;
+    // null if the device can send either report format
+    stepCounts: boolean | null;
+  };
+};
+
+// Payload of the toggle-changed event
+export type ToggleChangedEvent = {
+  id: string;
+  on: boolean;
+};
+
+// Payload of the macro-log event
+export type MacroLogEvent = {
+  operation: string;
+  exitCode: number | null;
+  stdout: string;
+  stderr: string;
+  error: string | null;
+};
+
+export type FirmwareUpdateProgressEvent = {
+  stage: 'rebooting' | 'downloading' | 'done';
+  bytesWritten: number;
+  totalBytes: number;
+};